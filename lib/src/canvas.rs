@@ -1,5 +1,8 @@
 use std::default::Default;
 
+use crate::shape::Shape;
+use crate::tileset::Tileset;
+
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub struct Canvas {
     pub width: u32,
@@ -36,47 +39,95 @@ impl Canvas {
     }
 
     pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile) {
+        self.replace_tile(x, y, tile);
+    }
+
+    /// Sets a tile, returning the change if the tile was in bounds and different.
+    fn replace_tile(&mut self, x: u32, y: u32, tile: Tile) -> Option<TileChange> {
         if x >= self.width || y >= self.height {
-            return;
+            return None;
+        }
+        let slot = &mut self.tiles[x as usize + y as usize * self.width as usize];
+        if *slot == tile {
+            return None;
         }
-        self.tiles[x as usize + y as usize * self.width as usize] = tile;
+        let before = std::mem::replace(slot, tile);
+        Some(TileChange {
+            x,
+            y,
+            before,
+            after: tile,
+        })
+    }
+
+    /// Fills the area of identical tiles around (`x`, `y`), through their sides.
+    pub fn flood_fill(&mut self, x: u32, y: u32, tile: Tile) {
+        self.fill(x, y, tile, FillOptions::default(), &mut Vec::new());
     }
 
-    pub fn flood_fill(&mut self, mut x: u32, mut y: u32, tile: Tile) {
+    /// Fills the tiles around (`x`, `y`) that match it in the way the options ask for.
+    pub fn flood_fill_with(&mut self, x: u32, y: u32, tile: Tile, options: FillOptions) {
+        self.fill(x, y, tile, options, &mut Vec::new());
+    }
+
+    fn fill(
+        &mut self,
+        x: u32,
+        y: u32,
+        tile: Tile,
+        options: FillOptions,
+        changes: &mut Vec<TileChange>,
+    ) {
         use std::collections::VecDeque;
-        if x >= self.width {
-            x = self.width - 1;
+        // An empty canvas has no tile to start from.
+        if self.tiles.is_empty() {
+            return;
         }
-        if y >= self.height {
-            y = self.height - 1;
+        let (x, y) = (x.min(self.width - 1), y.min(self.height - 1));
+        let target = self.get_tile(x, y);
+        let matches = |other: Tile| options.matching.matches(other, target);
+        if options.reach == FillReach::Everywhere {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    if matches(self.get_tile(x, y)) {
+                        changes.extend(self.replace_tile(x, y, tile));
+                    }
+                }
+            }
+            return;
         }
 
+        let neighbours: &[(i64, i64)] = match options.reach {
+            FillReach::EightWay => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+            _ => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+        };
+        // Filled tiles may still match, so remember where the fill has been.
+        let mut visited = vec![false; self.tiles.len()];
         let mut queue = VecDeque::new();
-        let old_tile = self.get_tile(x, y);
-        if old_tile == tile {
-            return;
-        }
+        visited[x as usize + y as usize * self.width as usize] = true;
         queue.push_back((x, y));
         while let Some((x, y)) = queue.pop_front() {
-            if self.get_tile(x, y) != old_tile {
-                continue;
-            }
-            self.set_tile(x, y, tile);
-            if x == 0 {
-                queue.push_back((1, y));
-            } else if x == self.width - 1 {
-                queue.push_back((x - 1, y));
-            } else {
-                queue.push_back((x - 1, y));
-                queue.push_back((x + 1, y));
-            }
-            if y == 0 {
-                queue.push_back((x, 1));
-            } else if y == self.height - 1 {
-                queue.push_back((x, y - 1));
-            } else {
-                queue.push_back((x, y - 1));
-                queue.push_back((x, y + 1));
+            changes.extend(self.replace_tile(x, y, tile));
+            for (dx, dy) in neighbours {
+                let (x, y) = (x as i64 + dx, y as i64 + dy);
+                if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+                    continue;
+                }
+                let (x, y) = (x as u32, y as u32);
+                let index = x as usize + y as usize * self.width as usize;
+                if !visited[index] && matches(self.tiles[index]) {
+                    visited[index] = true;
+                    queue.push_back((x, y));
+                }
             }
         }
     }
@@ -86,11 +137,146 @@ impl Canvas {
         self.tiles = tiles;
     }
 
-    pub fn handle_action(&mut self, action: Action) {
+    /// Changes the size of the canvas, keeping the existing tiles lined up with `anchor` and
+    /// filling any new space with `fill`. Both dimensions are at least 1.
+    pub fn resize(&mut self, width: u32, height: u32, anchor: Anchor, fill: Tile) {
+        let (width, height) = (width.max(1), height.max(1));
+        let offset = anchor.offset(self.size(), (width, height));
+        self.reframe(width, height, offset, fill);
+    }
+
+    /// Cuts the canvas down to the part of `region` that lies within it.
+    pub fn crop(&mut self, region: Region) {
+        let Some(region) = region.clamp(self.width, self.height) else {
+            return;
+        };
+        let offset = (-(region.x as i64), -(region.y as i64));
+        self.reframe(region.width, region.height, offset, Tile::default());
+    }
+
+    /// A copy of the tiles in the part of `region` inside the canvas, if there is any.
+    pub fn extract_region(&self, region: Region) -> Option<Canvas> {
+        let region = region.clamp(self.width, self.height)?;
+        let mut tiles = Vec::with_capacity(region.width as usize * region.height as usize);
+        for y in region.y..region.y + region.height {
+            let start = region.x as usize + y as usize * self.width as usize;
+            tiles.extend_from_slice(&self.tiles[start..start + region.width as usize]);
+        }
+        Some(Canvas {
+            width: region.width,
+            height: region.height,
+            tiles,
+        })
+    }
+
+    /// Copies `tiles` onto the canvas with their top left corner at (`x`, `y`). Tiles falling
+    /// outside the canvas are dropped.
+    pub fn paste_region(&mut self, x: i32, y: i32, tiles: &Canvas) {
+        self.paste(x, y, tiles, &mut Vec::new());
+    }
+
+    fn paste(&mut self, x: i32, y: i32, tiles: &Canvas, changes: &mut Vec<TileChange>) {
+        for (tile_x, tile_y, &tile) in tiles.tiles() {
+            let (x, y) = (x as i64 + tile_x as i64, y as i64 + tile_y as i64);
+            if x >= 0 && y >= 0 && x <= u32::MAX as i64 && y <= u32::MAX as i64 {
+                changes.extend(self.replace_tile(x as u32, y as u32, tile));
+            }
+        }
+    }
+
+    /// Sets every tile in the part of `region` inside the canvas.
+    pub fn fill_region(&mut self, region: Region, tile: Tile) {
+        self.fill_rect(region, tile, &mut Vec::new());
+    }
+
+    fn fill_rect(&mut self, region: Region, tile: Tile, changes: &mut Vec<TileChange>) {
+        let Some(region) = region.clamp(self.width, self.height) else {
+            return;
+        };
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                changes.extend(self.replace_tile(x, y, tile));
+            }
+        }
+    }
+
+    /// Moves the tiles into a canvas of a new size, with the old top left corner at `offset`.
+    fn reframe(&mut self, width: u32, height: u32, offset: (i64, i64), fill: Tile) {
+        let mut tiles = vec![fill; width as usize * height as usize];
+        for (x, y, tile) in self.tiles() {
+            let (x, y) = (x as i64 + offset.0, y as i64 + offset.1);
+            if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                tiles[x as usize + y as usize * width as usize] = *tile;
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.tiles = tiles;
+    }
+
+    /// The smallest region holding every tile that shows more than the top left tile's
+    /// background colour, or `None` if there is nothing else on the canvas.
+    pub fn trim_region(&self, tileset: &Tileset) -> Option<Region> {
+        let background = self.get_tile(0, 0).bg;
+        let is_empty = |tile: &Tile| {
+            tile.bg == background && (tile.fg == background || tileset.is_blank(tile.index))
+        };
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, tile) in self.tiles() {
+            if is_empty(tile) {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+        let (left, top, right, bottom) = bounds?;
+        Some(Region {
+            x: left,
+            y: top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        })
+    }
+
+    /// Applies an action, returning every tile it changed.
+    ///
+    /// Actions that change the canvas size replace every tile and return no changes, so they are
+    /// recorded as snapshots of the whole canvas by [`History`](crate::history::History).
+    pub fn handle_action(&mut self, action: Action) -> Vec<TileChange> {
+        let mut changes = Vec::new();
         match action {
-            Action::SetTile { x, y, tile } => self.set_tile(x, y, tile),
-            Action::FloodFill { x, y, tile } => self.flood_fill(x, y, tile),
+            Action::SetTile { x, y, tile } => changes.extend(self.replace_tile(x, y, tile)),
+            Action::FloodFill {
+                x,
+                y,
+                tile,
+                options,
+            } => self.fill(x, y, tile, options, &mut changes),
+            Action::Resize {
+                width,
+                height,
+                anchor,
+                fill,
+            } => self.resize(width, height, anchor, fill),
+            Action::Crop(region) => self.crop(region),
+            Action::Paste { x, y, tiles } => self.paste(x, y, &tiles, &mut changes),
+            Action::FillRegion { region, tile } => self.fill_rect(region, tile, &mut changes),
+            Action::Shape {
+                shape,
+                start,
+                end,
+                tile,
+            } => {
+                for (x, y) in shape.points(start, end) {
+                    changes.extend(self.replace_tile(x, y, tile));
+                }
+            }
         }
+        changes
     }
 }
 
@@ -125,13 +311,17 @@ pub struct Color {
 }
 
 impl Color {
-    pub fn rgb(r: u8, g: u8, b: u8) -> Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
         Color { r, g, b }
     }
     pub fn to_argb(&self) -> u32 {
         let (r, g, b) = (self.r as u32, self.g as u32, self.b as u32);
         (0xFF << 24) | (r << 16) | (g << 8) | b
     }
+    /// The colour as a CSS-style `#rrggbb` string.
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
 }
 
 #[cfg(feature = "libcosmic")]
@@ -158,8 +348,415 @@ impl From<Color> for image::Rgba<u8> {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum Action {
-    SetTile { x: u32, y: u32, tile: Tile },
-    FloodFill { x: u32, y: u32, tile: Tile },
+    SetTile {
+        x: u32,
+        y: u32,
+        tile: Tile,
+    },
+    FloodFill {
+        x: u32,
+        y: u32,
+        tile: Tile,
+        options: FillOptions,
+    },
+    Resize {
+        width: u32,
+        height: u32,
+        anchor: Anchor,
+        fill: Tile,
+    },
+    Crop(Region),
+    Paste {
+        x: i32,
+        y: i32,
+        tiles: Canvas,
+    },
+    FillRegion {
+        region: Region,
+        tile: Tile,
+    },
+    /// Draws a shape between two corners, see [`Shape`].
+    Shape {
+        shape: Shape,
+        start: (u32, u32),
+        end: (u32, u32),
+        tile: Tile,
+    },
+}
+
+impl Action {
+    /// Whether the action can change the size of the canvas.
+    pub fn resizes(&self) -> bool {
+        matches!(self, Action::Resize { .. } | Action::Crop(_))
+    }
+}
+
+/// A part of a tile: what a fill compares tiles by, and what the eyedropper copies.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TilePart {
+    #[default]
+    Tile,
+    Index,
+    Foreground,
+    Background,
+}
+
+impl TilePart {
+    pub const ALL: [TilePart; 4] = [
+        TilePart::Tile,
+        TilePart::Index,
+        TilePart::Foreground,
+        TilePart::Background,
+    ];
+
+    /// Whether `a` and `b` are the same in this part.
+    fn matches(self, a: Tile, b: Tile) -> bool {
+        match self {
+            TilePart::Tile => a == b,
+            TilePart::Index => a.index == b.index,
+            TilePart::Foreground => a.fg == b.fg,
+            TilePart::Background => a.bg == b.bg,
+        }
+    }
+
+    /// The current tile with this part taken from `picked`.
+    pub fn pick(self, current: Tile, picked: Tile) -> Tile {
+        match self {
+            TilePart::Tile => picked,
+            TilePart::Index => Tile {
+                index: picked.index,
+                ..current
+            },
+            TilePart::Foreground => Tile {
+                fg: picked.fg,
+                ..current
+            },
+            TilePart::Background => Tile {
+                bg: picked.bg,
+                ..current
+            },
+        }
+    }
+}
+
+/// How a fill spreads from the clicked tile.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FillReach {
+    /// Through the sides of tiles.
+    #[default]
+    FourWay,
+    /// Through the sides and corners of tiles.
+    EightWay,
+    /// To every matching tile on the canvas, connected or not.
+    Everywhere,
+}
+
+impl FillReach {
+    pub const ALL: [FillReach; 3] = [
+        FillReach::FourWay,
+        FillReach::EightWay,
+        FillReach::Everywhere,
+    ];
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FillOptions {
+    pub matching: TilePart,
+    pub reach: FillReach,
+}
+
+/// The part of a resized canvas that keeps the existing tiles.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Every anchor, row by row from the top left.
+    pub const ALL: [Anchor; 9] = [
+        Anchor::TopLeft,
+        Anchor::Top,
+        Anchor::TopRight,
+        Anchor::Left,
+        Anchor::Center,
+        Anchor::Right,
+        Anchor::BottomLeft,
+        Anchor::Bottom,
+        Anchor::BottomRight,
+    ];
+
+    /// Where the old top left corner ends up when resizing from `old` to `new`.
+    fn offset(self, old: (u32, u32), new: (u32, u32)) -> (i64, i64) {
+        let index = Anchor::ALL
+            .iter()
+            .position(|&anchor| anchor == self)
+            .unwrap();
+        let along = |position: usize, old: u32, new: u32| {
+            let space = new as i64 - old as i64;
+            match position {
+                0 => 0,
+                1 => space / 2,
+                _ => space,
+            }
+        };
+        (
+            along(index % 3, old.0, new.0),
+            along(index / 3, old.1, new.1),
+        )
+    }
+}
+
+/// A rectangle of tiles.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// The region spanning two corners, both included.
+    pub fn from_corners(a: (u32, u32), b: (u32, u32)) -> Region {
+        let (x, y) = (a.0.min(b.0), a.1.min(b.1));
+        Region {
+            x,
+            y,
+            width: a.0.max(b.0) - x + 1,
+            height: a.1.max(b.1) - y + 1,
+        }
+    }
+
+    /// The part of the region inside a canvas of the given size, if there is any.
+    pub fn clamp(self, width: u32, height: u32) -> Option<Region> {
+        let right = self.x.saturating_add(self.width).min(width);
+        let bottom = self.y.saturating_add(self.height).min(height);
+        if self.x >= right || self.y >= bottom {
+            return None;
+        }
+        Some(Region {
+            x: self.x,
+            y: self.y,
+            width: right - self.x,
+            height: bottom - self.y,
+        })
+    }
+}
+
+/// A single tile overwritten by an action, with enough information to revert it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TileChange {
+    pub x: u32,
+    pub y: u32,
+    pub before: Tile,
+    pub after: Tile,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::History;
+
+    const RED: Color = Color::rgb(255, 0, 0);
+    const GREEN: Color = Color::rgb(0, 255, 0);
+    const BLUE: Color = Color::rgb(0, 0, 255);
+
+    /// A canvas with one tile per character, its index being the character.
+    fn from_rows(rows: &[&str]) -> Canvas {
+        let mut canvas = Canvas::new(rows[0].len() as u32, rows.len() as u32);
+        let tiles = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|c| Tile {
+                index: c as u32,
+                ..Tile::default()
+            })
+            .collect();
+        canvas.set_all_tiles(tiles);
+        canvas
+    }
+
+    fn to_rows(canvas: &Canvas) -> Vec<String> {
+        (0..canvas.height)
+            .map(|y| {
+                (0..canvas.width)
+                    .map(|x| char::from_u32(canvas.get_tile(x, y).index).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn letter(c: char) -> Tile {
+        Tile {
+            index: c as u32,
+            ..Tile::default()
+        }
+    }
+
+    fn fill_reach(reach: FillReach) -> Vec<String> {
+        let mut canvas = from_rows(&["aaba", "abaa", "bbbb", "aaba"]);
+        let options = FillOptions {
+            reach,
+            ..FillOptions::default()
+        };
+        canvas.flood_fill_with(0, 0, letter('#'), options);
+        to_rows(&canvas)
+    }
+
+    #[test]
+    fn fill_four_way_spreads_through_sides() {
+        assert_eq!(
+            fill_reach(FillReach::FourWay),
+            ["##ba", "#baa", "bbbb", "aaba"]
+        );
+    }
+
+    #[test]
+    fn fill_eight_way_spreads_through_corners() {
+        assert_eq!(
+            fill_reach(FillReach::EightWay),
+            ["##b#", "#b##", "bbbb", "aaba"]
+        );
+    }
+
+    #[test]
+    fn fill_everywhere_covers_unconnected_tiles() {
+        assert_eq!(
+            fill_reach(FillReach::Everywhere),
+            ["##b#", "#b##", "bbbb", "##b#"]
+        );
+    }
+
+    #[test]
+    fn fill_matches_by_tile_part() {
+        let clicked = Tile {
+            index: 'A' as u32,
+            fg: RED,
+            bg: BLUE,
+        };
+        let others = [
+            Tile {
+                fg: GREEN,
+                bg: GREEN,
+                ..clicked
+            },
+            Tile {
+                index: 'B' as u32,
+                bg: GREEN,
+                ..clicked
+            },
+            Tile {
+                index: 'B' as u32,
+                fg: GREEN,
+                ..clicked
+            },
+            clicked,
+        ];
+        let expected = [
+            (TilePart::Tile, [false, false, false, true]),
+            (TilePart::Index, [true, false, false, true]),
+            (TilePart::Foreground, [false, true, false, true]),
+            (TilePart::Background, [false, false, true, true]),
+        ];
+        for (matching, expected) in expected {
+            let mut canvas = Canvas::new(5, 1);
+            let mut tiles = vec![clicked];
+            tiles.extend(others);
+            canvas.set_all_tiles(tiles);
+            let options = FillOptions {
+                matching,
+                reach: FillReach::Everywhere,
+            };
+            canvas.flood_fill_with(0, 0, letter('#'), options);
+            assert_eq!(canvas.get_tile(0, 0), letter('#'));
+            let filled: Vec<bool> = (1..5)
+                .map(|x| canvas.get_tile(x, 0) == letter('#'))
+                .collect();
+            assert_eq!(filled, expected, "{matching:?}");
+        }
+    }
+
+    #[test]
+    fn fill_stops_when_filled_tiles_still_match() {
+        let mut canvas = from_rows(&["aab", "aba", "aaa"]);
+        let options = FillOptions {
+            matching: TilePart::Foreground,
+            reach: FillReach::FourWay,
+        };
+        canvas.flood_fill_with(2, 2, letter('#'), options);
+        assert_eq!(to_rows(&canvas), ["###", "###", "###"]);
+    }
+
+    #[test]
+    fn fill_on_empty_canvas_does_nothing() {
+        for (width, height) in [(0, 0), (0, 3), (3, 0)] {
+            let mut canvas = Canvas::new(width, height);
+            canvas.flood_fill(0, 0, Tile::default());
+            assert!(canvas.tiles.is_empty());
+        }
+    }
+
+    #[test]
+    fn resize_grows_around_anchor() {
+        let expected = [
+            ["ab..", "cd..", "....", "...."],
+            [".ab.", ".cd.", "....", "...."],
+            ["..ab", "..cd", "....", "...."],
+            ["....", "ab..", "cd..", "...."],
+            ["....", ".ab.", ".cd.", "...."],
+            ["....", "..ab", "..cd", "...."],
+            ["....", "....", "ab..", "cd.."],
+            ["....", "....", ".ab.", ".cd."],
+            ["....", "....", "..ab", "..cd"],
+        ];
+        for (anchor, expected) in Anchor::ALL.into_iter().zip(expected) {
+            let mut canvas = from_rows(&["ab", "cd"]);
+            canvas.resize(4, 4, anchor, letter('.'));
+            assert_eq!(to_rows(&canvas), expected, "{anchor:?}");
+        }
+    }
+
+    #[test]
+    fn resize_shrinks_towards_anchor() {
+        for (anchor, expected) in Anchor::ALL.into_iter().zip("abcdefghi".chars()) {
+            let mut canvas = from_rows(&["abc", "def", "ghi"]);
+            canvas.resize(1, 1, anchor, letter('.'));
+            assert_eq!(to_rows(&canvas), [expected.to_string()], "{anchor:?}");
+        }
+        // Growing one way and shrinking the other.
+        let mut canvas = from_rows(&["abcd", "efgh"]);
+        canvas.resize(2, 4, Anchor::BottomRight, letter('.'));
+        assert_eq!(to_rows(&canvas), ["..", "..", "cd", "gh"]);
+    }
+
+    #[test]
+    fn resize_can_be_undone() {
+        let original = from_rows(&["ab", "cd"]);
+        let mut canvas = original.clone();
+        let mut history = History::default();
+        let action = Action::Resize {
+            width: 3,
+            height: 1,
+            anchor: Anchor::BottomRight,
+            fill: letter('.'),
+        };
+        assert!(history.apply(&mut canvas, action));
+        let resized = canvas.clone();
+        assert_eq!(to_rows(&resized), [".cd"]);
+        assert!(history.undo(&mut canvas));
+        assert_eq!(canvas, original);
+        assert!(history.redo(&mut canvas));
+        assert_eq!(canvas, resized);
+    }
 }
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+/// Code page 437, the IBM PC character set, with the graphical symbols for control codes.
+/// Index 0 is left unmapped.
+const CP437: &str = "\0☺☻♥♦♣♠•◘○◙♂♀♪♫☼►◄↕‼¶§▬↨↑↓→←∟↔▲▼ !\"#$%&'()*+,-./0123456789:;<=>?\
+    @ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~⌂\
+    ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐\
+    └┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+/// Commodore 64 screen codes 0-127 of the upper case/graphics set. Codes 128-255 are the same
+/// glyphs in reverse video, which Unicode has no characters for, so they are left unmapped.
+/// Graphics missing from the basic multilingual plane use the Symbols for Legacy Computing block.
+const PETSCII: &str = "@ABCDEFGHIJKLMNOPQRSTUVWXYZ[£]↑← !\"#$%&'()*+,-./0123456789:;<=>?\
+    ─♠\u{1fb72}\u{1fb78}\u{1fb77}\u{1fb76}\u{1fb7a}\u{1fb71}\u{1fb74}╮╰╯\u{1fb7c}╲╱\u{1fb7d}\
+    \u{1fb7e}●\u{1fb7b}♥\u{1fb70}╭╳○♣\u{1fb75}♦┼\u{1fb8c}│π◥\
+    \u{a0}▌▄▔▁▏▒▕\u{1fb8f}◤\u{1fb87}├▗└┐▂┌┴┬┤▎▍\u{1fb88}\u{1fb82}\u{1fb83}▃\u{1fb7f}▖▝┘▘▚";
+
+#[derive(Debug, Error)]
+pub enum CharMapError {
+    #[error("couldn't read character map: {0}")]
+    Io(#[from] io::Error),
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// Mapping between tile indices and the Unicode characters they depict.
+///
+/// A tile can stand for several characters (a font's `A` glyph may also be used for the Greek
+/// `Α`), and the same character can appear on several tiles. [`CharMap::char`] gives the first
+/// character inserted for a tile, and [`CharMap::index`] the first tile inserted for a character.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CharMap {
+    /// Sparse, as fonts and mapping tables can use any index.
+    chars: BTreeMap<u32, char>,
+    indices: HashMap<char, u32>,
+}
+
+impl CharMap {
+    pub fn new() -> CharMap {
+        CharMap::default()
+    }
+
+    /// Printable ASCII at its own code, the layout of the built-in tileset.
+    pub fn ascii() -> CharMap {
+        CharMap::from_table(CP437)
+            .into_iter()
+            .filter(|&(index, _)| (0x20..0x7F).contains(&index))
+            .collect()
+    }
+
+    pub fn cp437() -> CharMap {
+        CharMap::from_table(CP437).into_iter().collect()
+    }
+
+    pub fn petscii() -> CharMap {
+        CharMap::from_table(PETSCII).into_iter().collect()
+    }
+
+    fn from_table(table: &str) -> Vec<(u32, char)> {
+        table
+            .chars()
+            .enumerate()
+            .filter(|&(_, c)| c != '\0')
+            .map(|(index, c)| (index as u32, c))
+            .collect()
+    }
+
+    /// Parses a mapping table in the format of the Unicode consortium's code page files.
+    ///
+    /// Each line holds a tile index and a code point, as in `0x41 0x0041 # LATIN CAPITAL A`.
+    /// Numbers may be hexadecimal with a `0x` prefix or decimal, and code points may also be
+    /// written as `U+0041`. Everything after `#` is a comment, and lines with only an index
+    /// (unmapped codes) are ignored.
+    pub fn parse(text: &str) -> Result<CharMap, CharMapError> {
+        let mut charmap = CharMap::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let error = |message: &str| CharMapError::Syntax {
+                line: line_number + 1,
+                message: message.into(),
+            };
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let (Some(index), Some(code)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let index = parse_number(index).ok_or_else(|| error("invalid tile index"))?;
+            let code = parse_number(code).ok_or_else(|| error("invalid code point"))?;
+            let c = char::from_u32(code).ok_or_else(|| error("invalid code point"))?;
+            charmap.insert(index, c);
+        }
+        Ok(charmap)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<CharMap, CharMapError> {
+        CharMap::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn insert(&mut self, index: u32, c: char) {
+        self.chars.entry(index).or_insert(c);
+        self.indices.entry(c).or_insert(index);
+    }
+
+    /// The character shown by a tile.
+    pub fn char(&self, index: u32) -> Option<char> {
+        self.chars.get(&index).copied()
+    }
+
+    /// The tile showing a character.
+    pub fn index(&self, c: char) -> Option<u32> {
+        self.indices.get(&c).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Every mapping, in an order that rebuilds the same map when inserted again.
+    pub fn entries(&self) -> Vec<(u32, char)> {
+        let mut entries: Vec<(u32, char)> =
+            self.chars.iter().map(|(&index, &c)| (index, c)).collect();
+        let mut aliases: Vec<(u32, char)> = self
+            .indices
+            .iter()
+            .filter(|&(&c, &index)| self.char(index) != Some(c))
+            .map(|(&c, &index)| (index, c))
+            .collect();
+        aliases.sort_unstable();
+        entries.extend(aliases);
+        entries
+    }
+}
+
+impl FromIterator<(u32, char)> for CharMap {
+    fn from_iter<I: IntoIterator<Item = (u32, char)>>(iter: I) -> CharMap {
+        let mut charmap = CharMap::new();
+        for (index, c) in iter {
+            charmap.insert(index, c);
+        }
+        charmap
+    }
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix("U+"));
+    match hex {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_indices_are_kept() {
+        let charmap = CharMap::parse("0xFFFFFFF0 0x41\n0x42 0x42\n0x43 0x42").unwrap();
+        assert_eq!(charmap.char(0xFFFF_FFF0), Some('A'));
+        assert_eq!(charmap.index('A'), Some(0xFFFF_FFF0));
+        assert_eq!(charmap.char(0x43), Some('B'));
+        assert_eq!(charmap.index('B'), Some(0x42));
+        assert_eq!(charmap.entries().into_iter().collect::<CharMap>(), charmap);
+    }
+}
@@ -0,0 +1,264 @@
+//! Converting pictures into canvases, picking the tile and colours that best match each cell.
+//!
+//! A tile drawn with coverage `a` shows `bg + a * (fg - bg)` at each pixel, which is linear in
+//! the coverage. For every glyph, the foreground and background that fit the cell's pixels best
+//! are found with a least-squares fit, and the glyph whose fit has the smallest error wins.
+
+use image::imageops::{self, FilterType};
+use image::{GenericImageView, Rgb, RgbImage};
+
+use crate::canvas::{Canvas, Color, Tile};
+use crate::render::{self, RenderError};
+use crate::tileset::Tileset;
+
+/// How the difference between the source pixels and a tile is measured.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// Sum of squared channel differences. Favours avoiding large errors.
+    #[default]
+    Squared,
+    /// Sum of absolute channel differences. More tolerant of a few badly matched pixels.
+    Absolute,
+    /// Squared difference in brightness only, which matches shapes over hues.
+    Luma,
+}
+
+impl Metric {
+    fn error(self, a: [f32; 3], b: [f32; 3]) -> f32 {
+        match self {
+            Metric::Squared => (0..3).map(|i| (a[i] - b[i]).powi(2)).sum(),
+            Metric::Absolute => (0..3).map(|i| (a[i] - b[i]).abs()).sum(),
+            Metric::Luma => (luma(a) - luma(b)).powi(2),
+        }
+    }
+}
+
+fn luma(c: [f32; 3]) -> f32 {
+    0.299 * c[0] + 0.587 * c[1] + 0.114 * c[2]
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConvertOptions {
+    /// Width of the canvas in tiles. The height keeps the picture's aspect ratio. Without a
+    /// width, every tile covers its size in pixels of the picture.
+    pub width: Option<u32>,
+    pub metric: Metric,
+    /// Colours the tiles may use. Without a palette, any colour can be used.
+    pub palette: Option<Vec<Color>>,
+}
+
+/// A glyph's coverage, with the sums the least-squares fit needs.
+struct Glyph {
+    index: u32,
+    coverage: Vec<f32>,
+    sum: f32,
+    sum_squares: f32,
+}
+
+fn glyphs(tileset: &Tileset) -> Vec<Glyph> {
+    (0..tileset.tile_count())
+        .filter_map(|index| {
+            let tile = tileset.tile(index)?;
+            let coverage: Vec<f32> = tile
+                .pixels()
+                .map(|(_, _, p)| p.0[0] as f32 / 255.0)
+                .collect();
+            Some(Glyph {
+                index,
+                sum: coverage.iter().sum(),
+                sum_squares: coverage.iter().map(|a| a * a).sum(),
+                coverage,
+            })
+        })
+        .collect()
+}
+
+fn to_color(c: [f32; 3]) -> Color {
+    let channel = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    Color::rgb(channel(c[0]), channel(c[1]), channel(c[2]))
+}
+
+fn to_floats(color: Color) -> [f32; 3] {
+    [color.r as f32, color.g as f32, color.b as f32]
+}
+
+fn nearest(palette: &[Color], c: [f32; 3], metric: Metric) -> Color {
+    palette
+        .iter()
+        .copied()
+        .min_by(|&a, &b| {
+            let a = metric.error(to_floats(a), c);
+            let b = metric.error(to_floats(b), c);
+            a.total_cmp(&b)
+        })
+        .unwrap_or_else(|| to_color(c))
+}
+
+/// Finds the colours that make the glyph look most like the pixels.
+fn fit(glyph: &Glyph, pixels: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let n = pixels.len() as f32;
+    let denominator = n * glyph.sum_squares - glyph.sum * glyph.sum;
+    let mut fg = [0.0; 3];
+    let mut bg = [0.0; 3];
+    for channel in 0..3 {
+        let sum: f32 = pixels.iter().map(|p| p[channel]).sum();
+        if denominator.abs() < f32::EPSILON {
+            // Glyphs with even coverage can only show one colour.
+            fg[channel] = sum / n;
+            bg[channel] = sum / n;
+            continue;
+        }
+        let weighted: f32 = glyph
+            .coverage
+            .iter()
+            .zip(pixels)
+            .map(|(a, p)| a * p[channel])
+            .sum();
+        let difference = (n * weighted - glyph.sum * sum) / denominator;
+        bg[channel] = (sum - difference * glyph.sum) / n;
+        fg[channel] = bg[channel] + difference;
+    }
+    (fg, bg)
+}
+
+fn error(glyph: &Glyph, pixels: &[[f32; 3]], fg: Color, bg: Color, metric: Metric) -> f32 {
+    let (fg, bg) = (to_floats(fg), to_floats(bg));
+    glyph
+        .coverage
+        .iter()
+        .zip(pixels)
+        .map(|(&a, &p)| {
+            let shown = std::array::from_fn(|i| bg[i] + a * (fg[i] - bg[i]));
+            metric.error(shown, p)
+        })
+        .sum()
+}
+
+/// Converts a picture into a canvas drawn with the tileset.
+///
+/// Fails if the picture would have to be scaled to a size too large to hold.
+pub fn image_to_canvas(
+    image: &RgbImage,
+    tileset: &Tileset,
+    options: &ConvertOptions,
+) -> Result<Canvas, RenderError> {
+    let (tile_width, tile_height) = tileset.tile_size;
+    let (image_width, image_height) = image.dimensions();
+    let (width, height) = match options.width {
+        Some(width) => {
+            let width = width.max(1);
+            // Keep the aspect ratio in pixels, not in tiles.
+            let height = (image_height as f64 * width as f64 * tile_width as f64
+                / (image_width.max(1) as f64 * tile_height as f64))
+                .round() as u32;
+            (width, height.max(1))
+        }
+        None => (
+            (image_width / tile_width).max(1),
+            (image_height / tile_height).max(1),
+        ),
+    };
+    let (scaled_width, scaled_height) = render::image_size((width, height), tileset.tile_size)?;
+    let image = imageops::resize(image, scaled_width, scaled_height, FilterType::Triangle);
+
+    let glyphs = glyphs(tileset);
+    let palette = options
+        .palette
+        .as_deref()
+        .filter(|palette| !palette.is_empty());
+    let mut canvas = Canvas::new(width, height);
+    let mut tiles = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let cell = image.view(x * tile_width, y * tile_height, tile_width, tile_height);
+            let pixels: Vec<[f32; 3]> = cell
+                .pixels()
+                .map(|(_, _, Rgb(p))| p.map(|v| v as f32))
+                .collect();
+            let best = glyphs
+                .iter()
+                .map(|glyph| {
+                    let (fg, bg) = fit(glyph, &pixels);
+                    let (fg, bg) = match palette {
+                        Some(palette) => (
+                            nearest(palette, fg, options.metric),
+                            nearest(palette, bg, options.metric),
+                        ),
+                        None => (to_color(fg), to_color(bg)),
+                    };
+                    let error = error(glyph, &pixels, fg, bg, options.metric);
+                    (
+                        error,
+                        Tile {
+                            index: glyph.index,
+                            fg,
+                            bg,
+                        },
+                    )
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+            tiles.push(best.map_or_else(Tile::default, |(_, tile)| tile));
+        }
+    }
+    canvas.set_all_tiles(tiles);
+    Ok(canvas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color::rgb(0xff, 0, 0);
+    const BLUE: Color = Color::rgb(0, 0, 0xff);
+
+    /// Two 2x1 tiles: blank, and the left pixel set.
+    fn tileset() -> Tileset {
+        let image = image::GrayImage::from_raw(4, 1, vec![0, 0, 255, 0]).unwrap();
+        Tileset::new(image, (2, 1)).unwrap()
+    }
+
+    #[test]
+    fn cells_get_the_best_tile_and_colours() {
+        let pixels = [RED, RED, BLUE, RED].map(Rgb::from);
+        let image = RgbImage::from_fn(4, 1, |x, _| pixels[x as usize]);
+        let canvas = image_to_canvas(&image, &tileset(), &ConvertOptions::default()).unwrap();
+        assert_eq!(canvas.size(), (2, 1));
+        // A solid cell fits every glyph, so the first one wins.
+        let solid = Tile {
+            index: 0,
+            fg: RED,
+            bg: RED,
+        };
+        assert_eq!(canvas.get_tile(0, 0), solid);
+        let split = Tile {
+            index: 1,
+            fg: BLUE,
+            bg: RED,
+        };
+        assert_eq!(canvas.get_tile(1, 0), split);
+    }
+
+    #[test]
+    fn colours_are_picked_from_the_palette() {
+        let image = RgbImage::from_pixel(4, 2, Rgb([0xf0, 0x10, 0x08]));
+        let options = ConvertOptions {
+            palette: Some(vec![BLUE, RED]),
+            ..ConvertOptions::default()
+        };
+        let canvas = image_to_canvas(&image, &tileset(), &options).unwrap();
+        assert_eq!(canvas.size(), (2, 2));
+        assert!(canvas
+            .tiles()
+            .all(|(_, _, tile)| tile.fg == RED && tile.bg == RED));
+    }
+
+    #[test]
+    fn huge_widths_are_rejected() {
+        let image = RgbImage::new(4, 1);
+        let options = ConvertOptions {
+            width: Some(u32::MAX),
+            ..ConvertOptions::default()
+        };
+        assert!(image_to_canvas(&image, &tileset(), &options).is_err());
+    }
+}
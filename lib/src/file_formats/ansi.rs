@@ -0,0 +1,577 @@
+//! ANSI art (`.ans`): CP437 text with escape sequences for colour and cursor movement.
+//!
+//! Tile indices are CP437 byte values. Colours use the 16-colour VGA palette where possible,
+//! with bold selecting the bright foreground colours and, with iCE colours, blink selecting the
+//! bright background colours. Other colours are written as PabloDraw's 24-bit `ESC[...t`
+//! sequences, and the reader also accepts xterm's 256-colour and 24-bit SGR parameters.
+
+use crate::canvas::{Canvas, Color, Tile};
+use crate::file_formats::LoadError;
+use crate::sauce::{self, Sauce};
+
+const ESC: u8 = 0x1B;
+/// End of file marker, followed by the SAUCE record if there is one.
+const SUB: u8 = 0x1A;
+
+/// Line width viewers assume when a file doesn't say otherwise.
+pub const DEFAULT_WIDTH: u32 = 80;
+
+/// Most cells a file can draw to. Cursor movement can reach far past the end of a file, so this
+/// keeps a few bytes from asking for an enormous canvas.
+const MAX_CELLS: u64 = 1 << 23;
+
+/// Default colours after `ESC[0m`, light grey on black.
+const DEFAULT_FG: u8 = 7;
+const DEFAULT_BG: u8 = 0;
+
+/// The VGA text mode palette, in ANSI order.
+pub const PALETTE: [Color; 16] = [
+    Color::rgb(0, 0, 0),
+    Color::rgb(170, 0, 0),
+    Color::rgb(0, 170, 0),
+    Color::rgb(170, 85, 0),
+    Color::rgb(0, 0, 170),
+    Color::rgb(170, 0, 170),
+    Color::rgb(0, 170, 170),
+    Color::rgb(170, 170, 170),
+    Color::rgb(85, 85, 85),
+    Color::rgb(255, 85, 85),
+    Color::rgb(85, 255, 85),
+    Color::rgb(255, 255, 85),
+    Color::rgb(85, 85, 255),
+    Color::rgb(255, 85, 255),
+    Color::rgb(85, 255, 255),
+    Color::rgb(255, 255, 255),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AnsiOptions {
+    /// Line width in characters. Text wraps to the next line after this many characters.
+    pub width: u32,
+    /// Number of lines, if known. Otherwise the canvas ends at the last line drawn to.
+    pub height: Option<u32>,
+    /// Whether blink selects the bright background colours instead of blinking.
+    pub ice_colors: bool,
+}
+
+impl Default for AnsiOptions {
+    fn default() -> Self {
+        AnsiOptions {
+            width: DEFAULT_WIDTH,
+            height: None,
+            ice_colors: true,
+        }
+    }
+}
+
+impl AnsiOptions {
+    /// Options for a file with the given SAUCE record, with defaults for what it doesn't say.
+    pub fn from_sauce(sauce: Option<&Sauce>) -> AnsiOptions {
+        let Some(sauce) = sauce else {
+            return AnsiOptions::default();
+        };
+        AnsiOptions {
+            width: sauce.width().unwrap_or(DEFAULT_WIDTH),
+            height: sauce.height(),
+            ice_colors: sauce.ice_colors(),
+        }
+    }
+}
+
+/// The SAUCE record describing a canvas written by [`write`], keeping the title, author and other
+/// details from an existing record.
+pub fn sauce(canvas: &Canvas, options: AnsiOptions, existing: Option<&Sauce>) -> Sauce {
+    let mut record = existing.cloned().unwrap_or_default();
+    record.data_type = sauce::DATA_TYPE_CHARACTER;
+    record.file_type = sauce::FILE_TYPE_ANSI;
+    let (width, height) = canvas.size();
+    record.info = [
+        width.try_into().unwrap_or(u16::MAX),
+        height.try_into().unwrap_or(u16::MAX),
+        0,
+        0,
+    ];
+    if options.ice_colors {
+        record.flags |= sauce::FLAG_NON_BLINK;
+    } else {
+        record.flags &= !sauce::FLAG_NON_BLINK;
+    }
+    record
+}
+
+/// The tile a cell has before anything is drawn to it.
+pub fn blank_tile() -> Tile {
+    Tile {
+        index: b' ' as u32,
+        fg: PALETTE[DEFAULT_FG as usize],
+        bg: PALETTE[DEFAULT_BG as usize],
+    }
+}
+
+/// Finds the palette entry for a colour, if it is exactly one of the VGA colours.
+pub fn palette_index(color: Color) -> Option<u8> {
+    PALETTE.iter().position(|&c| c == color).map(|i| i as u8)
+}
+
+/// Colour from the xterm 256-colour palette, with the first 16 entries taken from [`PALETTE`].
+pub fn color_256(index: u8) -> Color {
+    match index {
+        0..=15 => PALETTE[index as usize],
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let n = index - 16;
+            Color::rgb(level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        232..=255 => {
+            let level = 8 + (index - 232) * 10;
+            Color::rgb(level, level, level)
+        }
+    }
+}
+
+/// A colour as set by escape sequences.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Ink {
+    /// One of the 8 basic palette colours, made bright by bold or blink.
+    Palette(u8),
+    Rgb(Color),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Attributes {
+    fg: Ink,
+    bg: Ink,
+    bold: bool,
+    blink: bool,
+    inverse: bool,
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes {
+            fg: Ink::Palette(DEFAULT_FG),
+            bg: Ink::Palette(DEFAULT_BG),
+            bold: false,
+            blink: false,
+            inverse: false,
+        }
+    }
+}
+
+impl Attributes {
+    fn colors(&self, ice_colors: bool) -> (Color, Color) {
+        let fg = match self.fg {
+            Ink::Palette(n) => PALETTE[(n + if self.bold { 8 } else { 0 }) as usize],
+            Ink::Rgb(color) => color,
+        };
+        let bg = match self.bg {
+            Ink::Palette(n) => PALETTE[(n + if self.blink && ice_colors { 8 } else { 0 }) as usize],
+            Ink::Rgb(color) => color,
+        };
+        if self.inverse {
+            (bg, fg)
+        } else {
+            (fg, bg)
+        }
+    }
+
+    fn apply_sgr(&mut self, params: &[u32]) {
+        if params.is_empty() {
+            *self = Attributes::default();
+            return;
+        }
+        let mut params = params.iter().copied();
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Attributes::default(),
+                1 => self.bold = true,
+                5 | 6 => self.blink = true,
+                7 => self.inverse = true,
+                22 => self.bold = false,
+                25 => self.blink = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Ink::Palette((param - 30) as u8),
+                39 => self.fg = Ink::Palette(DEFAULT_FG),
+                40..=47 => self.bg = Ink::Palette((param - 40) as u8),
+                49 => self.bg = Ink::Palette(DEFAULT_BG),
+                90..=97 => self.fg = Ink::Rgb(PALETTE[(param - 90 + 8) as usize]),
+                100..=107 => self.bg = Ink::Rgb(PALETTE[(param - 100 + 8) as usize]),
+                38 | 48 => {
+                    let color = match params.next() {
+                        Some(5) => params.next().map(|n| color_256(n.min(255) as u8)),
+                        Some(2) => {
+                            let mut channel = || params.next().unwrap_or(0).min(255) as u8;
+                            Some(Color::rgb(channel(), channel(), channel()))
+                        }
+                        _ => None,
+                    };
+                    if let Some(color) = color {
+                        if param == 38 {
+                            self.fg = Ink::Rgb(color);
+                        } else {
+                            self.bg = Ink::Rgb(color);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+struct Screen {
+    width: u32,
+    rows: Vec<Vec<Tile>>,
+    x: u32,
+    y: u32,
+    /// Number of rows that have been drawn to, which becomes the canvas height.
+    height: u32,
+}
+
+impl Screen {
+    /// Draws at the cursor, wrapping to the next line as soon as the line is full, like
+    /// ansilove and PabloDraw do.
+    fn put(&mut self, tile: Tile) -> Result<(), LoadError> {
+        let x = self.x as usize;
+        self.row(self.y)?[x] = tile;
+        self.height = self.height.max(self.y + 1);
+        self.x += 1;
+        if self.x >= self.width {
+            self.x = 0;
+            self.y = self.y.saturating_add(1);
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, y: u32) -> Result<&mut Vec<Tile>, LoadError> {
+        let width = self.width as usize;
+        if y as usize >= self.rows.len() {
+            if (y as u64 + 1) * self.width as u64 > MAX_CELLS {
+                return Err(LoadError::InvalidDimensions {
+                    width,
+                    height: y as usize + 1,
+                });
+            }
+            self.rows
+                .resize_with(y as usize + 1, || vec![blank_tile(); width]);
+        }
+        Ok(&mut self.rows[y as usize])
+    }
+}
+
+/// Reads ANSI art, stopping at the end of file marker.
+///
+/// Fails if the picture would be unreasonably large.
+pub fn read(data: &[u8], options: AnsiOptions) -> Result<Canvas, LoadError> {
+    let mut screen = Screen {
+        width: options.width.max(1),
+        rows: Vec::new(),
+        x: 0,
+        y: 0,
+        height: 0,
+    };
+    let mut attributes = Attributes::default();
+    let mut saved = (0, 0);
+    let mut bytes = data.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            SUB => break,
+            b'\r' => screen.x = 0,
+            b'\n' => {
+                screen.x = 0;
+                screen.y = screen.y.saturating_add(1);
+            }
+            ESC if bytes.peek() == Some(&b'[') => {
+                bytes.next();
+                let mut sequence = Vec::new();
+                let mut command = None;
+                for byte in bytes.by_ref() {
+                    if (0x40..=0x7E).contains(&byte) {
+                        command = Some(byte);
+                        break;
+                    }
+                    sequence.push(byte);
+                }
+                let Some(command) = command else {
+                    break;
+                };
+                // Private sequences like `ESC[?7h` don't affect the picture.
+                if sequence.first() == Some(&b'?') {
+                    continue;
+                }
+                let params: Vec<u32> = String::from_utf8_lossy(&sequence)
+                    .split(';')
+                    .map(|param| param.parse().unwrap_or(0))
+                    .collect();
+                let count = params.first().copied().unwrap_or(0).max(1);
+                match command {
+                    b'm' => {
+                        if sequence.is_empty() {
+                            attributes.apply_sgr(&[]);
+                        } else {
+                            attributes.apply_sgr(&params);
+                        }
+                    }
+                    b'A' => screen.y = screen.y.saturating_sub(count),
+                    b'B' => screen.y = screen.y.saturating_add(count),
+                    b'C' => screen.x = screen.x.saturating_add(count).min(screen.width - 1),
+                    b'D' => screen.x = screen.x.saturating_sub(count),
+                    b'H' | b'f' => {
+                        screen.y = count - 1;
+                        screen.x =
+                            (params.get(1).copied().unwrap_or(1).max(1) - 1).min(screen.width - 1);
+                    }
+                    b's' => saved = (screen.x, screen.y),
+                    b'u' => (screen.x, screen.y) = saved,
+                    b'J' if params[0] == 2 => {
+                        screen.rows.clear();
+                        screen.height = 0;
+                        (screen.x, screen.y) = (0, 0);
+                    }
+                    b'K' => {
+                        let (_, bg) = attributes.colors(options.ice_colors);
+                        let x = screen.x.min(screen.width) as usize;
+                        let y = screen.y;
+                        for tile in &mut screen.row(y)?[x..] {
+                            *tile = Tile { bg, ..blank_tile() };
+                        }
+                    }
+                    // PabloDraw 24-bit colour: `ESC[0;R;G;Bt` for background, `ESC[1;R;G;Bt`
+                    // for foreground.
+                    b't' if params.len() == 4 => {
+                        let channel = |n: u32| n.min(255) as u8;
+                        let color =
+                            Color::rgb(channel(params[1]), channel(params[2]), channel(params[3]));
+                        if params[0] == 1 {
+                            attributes.fg = Ink::Rgb(color);
+                        } else {
+                            attributes.bg = Ink::Rgb(color);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                let (fg, bg) = attributes.colors(options.ice_colors);
+                screen.put(Tile {
+                    index: byte as u32,
+                    fg,
+                    bg,
+                })?;
+            }
+        }
+    }
+
+    let height = screen.height.max(options.height.unwrap_or(0)).max(1);
+    let mut canvas = Canvas::new(screen.width, height);
+    let mut tiles = Vec::with_capacity(canvas.tiles.len());
+    for y in 0..height {
+        tiles.extend_from_slice(screen.row(y)?);
+    }
+    canvas.set_all_tiles(tiles);
+    Ok(canvas)
+}
+
+/// Writes ANSI art, using as few escape sequences as it can.
+///
+/// Rows are as wide as the canvas. Trailing blank cells on a row are left out, and runs of
+/// blank cells inside a row are skipped over with cursor movement.
+pub fn write(canvas: &Canvas, options: AnsiOptions) -> Vec<u8> {
+    let blank = blank_tile();
+    let is_blank = |tile: &Tile| tile.index == blank.index && tile.bg == blank.bg;
+    let mut out = Vec::new();
+    let mut current = Attributes::default();
+    for y in 0..canvas.height {
+        let row: Vec<Tile> = (0..canvas.width).map(|x| canvas.get_tile(x, y)).collect();
+        let end = row
+            .iter()
+            .rposition(|tile| !is_blank(tile))
+            .map_or(0, |i| i + 1);
+        let mut x = 0;
+        while x < end {
+            let skip = row[x..end].iter().take_while(|tile| is_blank(tile)).count();
+            // `ESC[nC` is at least 4 bytes, so it only pays off for longer runs.
+            if skip >= 4 {
+                out.extend_from_slice(format!("\x1b[{skip}C").as_bytes());
+                x += skip;
+                continue;
+            }
+            let tile = row[x];
+            write_attributes(&mut out, &mut current, tile, options.ice_colors);
+            out.push(match u8::try_from(tile.index) {
+                // These would be read as control codes instead of glyphs.
+                Ok(b'\r' | b'\n' | ESC | SUB) | Err(_) => b' ',
+                Ok(byte) => byte,
+            });
+            x += 1;
+        }
+        // A full row has already wrapped the cursor onto the next line.
+        if end < canvas.width as usize {
+            out.extend_from_slice(b"\r\n");
+        }
+    }
+    if current != Attributes::default() {
+        out.extend_from_slice(b"\x1b[0m");
+    }
+    out
+}
+
+/// Emits the shortest sequences that switch from the current attributes to the tile's colours.
+fn write_attributes(out: &mut Vec<u8>, current: &mut Attributes, tile: Tile, ice_colors: bool) {
+    let mut target = *current;
+    match palette_index(tile.fg) {
+        Some(n) => {
+            target.fg = Ink::Palette(n % 8);
+            target.bold = n >= 8;
+        }
+        None => target.fg = Ink::Rgb(tile.fg),
+    }
+    match palette_index(tile.bg) {
+        Some(n) if n < 8 || ice_colors => {
+            target.bg = Ink::Palette(n % 8);
+            target.blink = n >= 8;
+        }
+        _ => target.bg = Ink::Rgb(tile.bg),
+    }
+    if target == *current {
+        return;
+    }
+
+    let mut params = Vec::new();
+    let mut from = *current;
+    // Bold and blink can only be turned off by resetting everything (or with the less widely
+    // supported 22 and 25).
+    if (from.bold && !target.bold) || (from.blink && !target.blink) {
+        params.push(0);
+        from = Attributes::default();
+    }
+    if target.bold && !from.bold {
+        params.push(1);
+    }
+    if target.blink && !from.blink {
+        params.push(5);
+    }
+    if let Ink::Palette(n) = target.fg {
+        if from.fg != target.fg {
+            params.push(30 + n as u32);
+        }
+    }
+    if let Ink::Palette(n) = target.bg {
+        if from.bg != target.bg {
+            params.push(40 + n as u32);
+        }
+    }
+    if !params.is_empty() {
+        let params: Vec<String> = params.iter().map(u32::to_string).collect();
+        out.extend_from_slice(format!("\x1b[{}m", params.join(";")).as_bytes());
+    }
+    // A reset also clears 24-bit colours.
+    if let Ink::Rgb(c) = target.fg {
+        if from.fg != target.fg {
+            out.extend_from_slice(format!("\x1b[1;{};{};{}t", c.r, c.g, c.b).as_bytes());
+        }
+    }
+    if let Ink::Rgb(c) = target.bg {
+        if from.bg != target.bg {
+            out.extend_from_slice(format!("\x1b[0;{};{};{}t", c.r, c.g, c.b).as_bytes());
+        }
+    }
+    // Bold and blink are kept as they were when only 24-bit colours are used.
+    *current = target;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letter(index: u8, fg: usize, bg: usize) -> Tile {
+        Tile {
+            index: index as u32,
+            fg: PALETTE[fg],
+            bg: PALETTE[bg],
+        }
+    }
+
+    fn round_trip(canvas: &Canvas) -> Canvas {
+        let options = AnsiOptions {
+            width: canvas.width,
+            height: Some(canvas.height),
+            ice_colors: true,
+        };
+        read(&write(canvas, options), options).unwrap()
+    }
+
+    #[test]
+    fn round_trip_keeps_tiles() {
+        let mut canvas = Canvas::new(12, 3);
+        let tiles = (0..36)
+            .map(|i| match i % 7 {
+                0..=2 => blank_tile(),
+                n => letter(b'a' + i as u8, n * 2 + 3, n - 3),
+            })
+            .collect();
+        canvas.set_all_tiles(tiles);
+        // Colours outside the palette use the 24-bit sequences.
+        canvas.set_tile(
+            5,
+            1,
+            Tile {
+                index: b'z' as u32,
+                fg: Color::rgb(1, 2, 3),
+                bg: Color::rgb(200, 100, 50),
+            },
+        );
+        assert_eq!(round_trip(&canvas), canvas);
+    }
+
+    #[test]
+    fn full_rows_stay_on_their_own_line() {
+        // A full row followed by a row that starts with skipped blank cells.
+        let mut canvas = Canvas::new(DEFAULT_WIDTH, 3);
+        let mut tiles = vec![letter(b'#', 7, 0); DEFAULT_WIDTH as usize];
+        tiles.extend(vec![blank_tile(); DEFAULT_WIDTH as usize * 2]);
+        tiles[DEFAULT_WIDTH as usize + 10] = letter(b'x', 15, 1);
+        tiles[DEFAULT_WIDTH as usize * 3 - 1] = letter(b'y', 2, 4);
+        canvas.set_all_tiles(tiles);
+        assert_eq!(round_trip(&canvas), canvas);
+
+        let read_back = read(
+            &write(&canvas, AnsiOptions::default()),
+            AnsiOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(read_back.size(), (DEFAULT_WIDTH, 3));
+    }
+
+    #[test]
+    fn full_width_rows_have_no_line_breaks() {
+        let mut canvas = Canvas::new(DEFAULT_WIDTH, 2);
+        let tiles = (0..DEFAULT_WIDTH * 2)
+            .map(|i| letter(b'A' + (i % 26) as u8, 7, 0))
+            .collect();
+        canvas.set_all_tiles(tiles);
+        let data = write(&canvas, AnsiOptions::default());
+        assert!(!data.windows(2).any(|pair| pair == b"\r\n"));
+        assert_eq!(read(&data, AnsiOptions::default()).unwrap(), canvas);
+    }
+
+    #[test]
+    fn text_wraps_at_the_line_width() {
+        let mut data = vec![b'a'; DEFAULT_WIDTH as usize];
+        data.extend_from_slice(b"\x1b[2Cb");
+        let canvas = read(&data, AnsiOptions::default()).unwrap();
+        assert_eq!(canvas.size(), (DEFAULT_WIDTH, 2));
+        assert_eq!(canvas.get_tile(DEFAULT_WIDTH - 1, 0).index, b'a' as u32);
+        assert_eq!(canvas.get_tile(2, 1).index, b'b' as u32);
+    }
+
+    #[test]
+    fn rejects_huge_cursor_movement() {
+        let options = AnsiOptions::default();
+        assert!(read(b"\x1b[4294967295BX", options).is_err());
+        assert!(read(b"\x1b[3000000HX", options).is_err());
+        // Moving right stops at the last column.
+        let canvas = read(b"\x1b[4294967295CX", options).unwrap();
+        assert_eq!(canvas.get_tile(DEFAULT_WIDTH - 1, 0).index, b'X' as u32);
+    }
+}
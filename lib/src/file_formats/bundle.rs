@@ -0,0 +1,155 @@
+//! `.montiz` bundles: a zip archive holding the canvas together with the tileset it was drawn
+//! with, so the file displays the same on machines that don't have that tileset.
+//!
+//! The archive contains:
+//! * `manifest.json`: a [`Manifest`] describing the other entries
+//! * `canvas.json`: the canvas, in the same [`SaveData`] layout as a `.monti` file
+//! * `tileset.png`: the tileset image, as 8-bit greyscale coverage
+
+use std::io::{Cursor, Read, Seek, Write};
+use std::sync::Arc;
+
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use super::{LoadError, SaveData, SaveError};
+use crate::charmap::CharMap;
+use crate::tileset::Tileset;
+
+/// Version of the bundle layout written by [`save`].
+pub const BUNDLE_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const CANVAS: &str = "canvas.json";
+const TILESET_IMAGE: &str = "tileset.png";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub canvas: String,
+    pub tileset: TilesetEntry,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TilesetEntry {
+    pub id: u64,
+    pub image: String,
+    pub tile_size: (u32, u32),
+    /// Tile index and character pairs, see [`CharMap::entries`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charmap: Option<Vec<(u32, char)>>,
+}
+
+pub fn save(
+    writer: impl Write + Seek,
+    data: &SaveData,
+    tileset: &Tileset,
+) -> Result<(), SaveError> {
+    let manifest = Manifest {
+        version: BUNDLE_VERSION,
+        canvas: CANVAS.into(),
+        tileset: TilesetEntry {
+            id: tileset.id,
+            image: TILESET_IMAGE.into(),
+            tile_size: tileset.tile_size,
+            charmap: tileset.charmap.as_ref().map(|charmap| charmap.entries()),
+        },
+    };
+    let mut image = Cursor::new(Vec::new());
+    tileset.image.write_to(&mut image, ImageFormat::Png)?;
+
+    let options = SimpleFileOptions::default();
+    let mut zip = ZipWriter::new(writer);
+    zip.start_file(MANIFEST, options)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest)?;
+    zip.start_file(CANVAS, options)?;
+    serde_json::to_writer(&mut zip, data)?;
+    // PNG is already compressed
+    zip.start_file(
+        TILESET_IMAGE,
+        options.compression_method(zip::CompressionMethod::Stored),
+    )?;
+    zip.write_all(image.get_ref())?;
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// Reads a bundle, leaving it to the caller to turn the save data into a canvas.
+pub fn load(reader: impl Read + Seek) -> Result<(SaveData, Tileset), LoadError> {
+    let mut zip = ZipArchive::new(reader)?;
+    let manifest: Manifest = serde_json::from_reader(zip.by_name(MANIFEST)?)?;
+    if manifest.version > BUNDLE_VERSION {
+        return Err(LoadError::UnsupportedVersion(manifest.version));
+    }
+    let data = SaveData::from_reader(zip.by_name(&manifest.canvas)?)?;
+
+    let mut tileset = match Tileset::builtin(manifest.tileset.id) {
+        Some(tileset) => tileset,
+        None => {
+            let mut png = Vec::new();
+            zip.by_name(&manifest.tileset.image)?
+                .read_to_end(&mut png)?;
+            let image = image::load_from_memory_with_format(&png, ImageFormat::Png)?;
+            Tileset::new(image.into_luma8(), manifest.tileset.tile_size)?
+        }
+    };
+    if let Some(entries) = manifest.tileset.charmap {
+        tileset.charmap = Some(Arc::new(entries.into_iter().collect::<CharMap>()));
+    }
+    Ok((data, tileset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Canvas, Color, Tile};
+
+    fn round_trip(canvas: &Canvas, tileset: &Tileset) -> (Canvas, Tileset) {
+        let mut file = Cursor::new(Vec::new());
+        save(&mut file, &SaveData::from_canvas(canvas), tileset).unwrap();
+        file.set_position(0);
+        let (data, tileset) = load(file).unwrap();
+        (data.to_canvas().unwrap(), tileset)
+    }
+
+    fn canvas() -> Canvas {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_tile(
+            2,
+            1,
+            Tile {
+                index: 1,
+                fg: Color::rgb(0x12, 0x34, 0x56),
+                bg: Color::rgb(0xab, 0xcd, 0xef),
+            },
+        );
+        canvas
+    }
+
+    #[test]
+    fn custom_tilesets_are_stored_in_the_bundle() {
+        let image = image::GrayImage::from_fn(6, 4, |x, y| image::Luma([(x * 40 + y) as u8]));
+        let tileset = Tileset::new(image, (3, 2)).unwrap();
+        let (loaded_canvas, loaded) = round_trip(&canvas(), &tileset);
+        assert_eq!(loaded_canvas, canvas());
+        assert!(loaded == tileset);
+        assert!(loaded.charmap.is_none());
+    }
+
+    #[test]
+    fn builtin_tilesets_keep_the_bundled_charmap() {
+        let mut charmap = CharMap::new();
+        charmap.insert(1, '☺');
+        charmap.insert(65, 'A');
+        let tileset = Tileset::default().with_charmap(charmap);
+        let (loaded_canvas, loaded) = round_trip(&canvas(), &tileset);
+        assert_eq!(loaded_canvas, canvas());
+        assert_eq!(loaded.id, Tileset::default().id);
+        assert!(loaded.image == tileset.image);
+        assert_eq!(loaded.char_for(1), Some('☺'));
+        assert_eq!(loaded.index_for('A'), Some(65));
+        assert_eq!(loaded.char_for(66), None);
+    }
+}
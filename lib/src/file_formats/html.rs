@@ -0,0 +1,84 @@
+//! Canvases as HTML, a `<pre>` element with a span for each run of tiles with the same colours.
+
+use std::fmt::Write;
+
+use super::text;
+use crate::canvas::Canvas;
+use crate::tileset::Tileset;
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The `<pre>` element on its own, for putting into other pages.
+pub fn to_pre(canvas: &Canvas, tileset: &Tileset) -> String {
+    let mut html =
+        String::from("<pre style=\"font-family: monospace; line-height: 1; letter-spacing: 0\">");
+    for y in 0..canvas.height {
+        let mut x = 0;
+        while x < canvas.width {
+            let first = canvas.get_tile(x, y);
+            let mut run = String::new();
+            while x < canvas.width {
+                let tile = canvas.get_tile(x, y);
+                if tile.fg != first.fg || tile.bg != first.bg {
+                    break;
+                }
+                run.push(text::char_for(tileset, tile.index));
+                x += 1;
+            }
+            write!(
+                html,
+                "<span style=\"color: {}; background: {}\">{}</span>",
+                first.fg.to_hex(),
+                first.bg.to_hex(),
+                escape(&run)
+            )
+            .unwrap();
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>");
+    html
+}
+
+/// A standalone page holding the canvas.
+pub fn to_html(canvas: &Canvas, tileset: &Tileset, title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body>\n{}\n</body>\n</html>\n",
+        escape(title),
+        to_pre(canvas, tileset)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Color, Tile};
+
+    #[test]
+    fn runs_of_the_same_colours_share_a_span() {
+        let red = Color::rgb(0xff, 0, 0);
+        let mut canvas = Canvas::new(3, 2);
+        for (x, c) in [(0, '<'), (1, 'a')] {
+            let tile = Tile {
+                index: c as u32,
+                fg: red,
+                ..Tile::default()
+            };
+            canvas.set_tile(x, 0, tile);
+        }
+        let pre = to_pre(&canvas, &Tileset::default());
+        assert_eq!(
+            pre,
+            "<pre style=\"font-family: monospace; line-height: 1; letter-spacing: 0\">\
+             <span style=\"color: #ff0000; background: #000000\">&lt;a</span>\
+             <span style=\"color: #ffffff; background: #000000\"> </span>\n\
+             <span style=\"color: #ffffff; background: #000000\">   </span>\n</pre>"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Upgrades older `.monti` layouts to the current [`SaveData`](super::SaveData) layout.
+//!
+//! Version 1 files have no `version` field. Every later version stores its number in `version`.
+//! To change the format, bump [`FORMAT_VERSION`] and append a migration from the previous version
+//! to [`MIGRATIONS`].
+
+use serde_json::{Map, Value};
+
+use super::LoadError;
+
+/// The version written by [`save`](super::save).
+pub const FORMAT_VERSION: u32 = 2;
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` object to version `n + 2`.
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize - 1] = [v1_to_v2];
+
+/// Version 2 only adds the `version` field itself.
+fn v1_to_v2(_data: &mut Map<String, Value>) {}
+
+/// Returns the format version of a parsed file.
+fn version(data: &Map<String, Value>) -> Result<u32, LoadError> {
+    match data.get("version") {
+        None => Ok(1),
+        Some(value) => value
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|&version| version >= 1)
+            .ok_or_else(|| LoadError::InvalidVersion(value.to_string())),
+    }
+}
+
+/// Upgrades a parsed file to [`FORMAT_VERSION`], rejecting files from newer versions.
+pub fn migrate(value: Value) -> Result<Value, LoadError> {
+    let mut data: Map<String, Value> = serde_json::from_value(value)?;
+    let version = version(&data)?;
+    if version > FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(&mut data);
+    }
+    data.insert("version".into(), FORMAT_VERSION.into());
+    Ok(Value::Object(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::file_formats::SaveData;
+
+    fn v1() -> Value {
+        json!({
+            "width": 2,
+            "height": 1,
+            "tiles": [65, 66],
+            "foreground": [0xFFFFFF, 0xFF0000],
+            "background": [0, 0x0000FF],
+        })
+    }
+
+    #[test]
+    fn v1_without_version_is_upgraded() {
+        let mut expected = v1();
+        expected["version"] = FORMAT_VERSION.into();
+        assert_eq!(migrate(v1()).unwrap(), expected);
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let data = SaveData::from_reader(v1().to_string().as_bytes()).unwrap();
+        let mut saved = Vec::new();
+        data.to_writer(&mut saved).unwrap();
+        let value: Value = serde_json::from_slice(&saved).unwrap();
+        assert_eq!(migrate(value.clone()).unwrap(), value);
+        let reloaded = SaveData::from_reader(saved.as_slice()).unwrap();
+        assert_eq!(reloaded.to_canvas().unwrap(), data.to_canvas().unwrap());
+    }
+
+    #[test]
+    fn newer_version_is_unsupported() {
+        let mut value = v1();
+        value["version"] = (FORMAT_VERSION + 1).into();
+        assert!(matches!(
+            migrate(value),
+            Err(LoadError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn non_integer_version_is_invalid() {
+        for version in [json!("2"), json!(1.5), json!(0), json!(-1)] {
+            let mut value = v1();
+            value["version"] = version;
+            assert!(matches!(migrate(value), Err(LoadError::InvalidVersion(_))));
+        }
+    }
+}
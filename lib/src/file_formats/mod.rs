@@ -1,13 +1,32 @@
 use std::borrow::Cow;
-use std::fs::File;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Cursor, Read, Write};
 use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use serde_json;
+use thiserror::Error;
 
 use crate::canvas::{Canvas, Color, Tile};
 use crate::file_state::FileState;
+use crate::font::FontError;
+use crate::history::History;
+use crate::sauce::Sauce;
+use crate::tileset::{Tileset, TilesetError};
+
+pub mod ansi;
+pub mod bundle;
+pub mod html;
+mod migration;
+pub mod rexpaint;
+pub mod svg;
+pub mod text;
+pub mod tiled;
+pub mod xbin;
+
+use ansi::AnsiOptions;
+pub use migration::FORMAT_VERSION;
+use tiled::MapFormat;
 
 #[derive(Debug)]
 pub struct Handle {
@@ -25,18 +44,192 @@ impl Handle {
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
 pub enum FileType {
     Monti,
+    /// Zip archive with the canvas and its tileset, see [`bundle`].
+    MontiBundle,
+    /// ANSI art, see [`ansi`].
+    Ansi,
+    /// XBin, see [`xbin`].
+    XBin,
+    /// REXPaint, see [`rexpaint`].
+    RexPaint,
+    /// Tiled map, see [`tiled`].
+    TiledXml,
+    TiledJson,
+}
+
+impl FileType {
+    /// Picks the file type from the extension, defaulting to [`FileType::Monti`].
+    pub fn from_path(path: impl AsRef<Path>) -> FileType {
+        let extension = path.as_ref().extension().and_then(|ext| ext.to_str());
+        match extension {
+            Some(ext) if ext.eq_ignore_ascii_case("montiz") => FileType::MontiBundle,
+            Some(ext) if ext.eq_ignore_ascii_case("ans") => FileType::Ansi,
+            Some(ext) if ext.eq_ignore_ascii_case("xb") => FileType::XBin,
+            Some(ext) if ext.eq_ignore_ascii_case("xp") => FileType::RexPaint,
+            Some(ext) if ext.eq_ignore_ascii_case("tmx") => FileType::TiledXml,
+            Some(ext) if ext.eq_ignore_ascii_case("tmj") => FileType::TiledJson,
+            _ => FileType::Monti,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            FileType::Monti => "monti",
+            FileType::MontiBundle => "montiz",
+            FileType::Ansi => "ans",
+            FileType::XBin => "xb",
+            FileType::RexPaint => "xp",
+            FileType::TiledXml => "tmx",
+            FileType::TiledJson => "tmj",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("couldn't read file: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid file contents: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid format version {0}")]
+    InvalidVersion(String),
+    #[error(
+        "file uses format version {0}, but only versions up to {FORMAT_VERSION} are supported"
+    )]
+    UnsupportedVersion(u32),
+    #[error("invalid canvas size {width}x{height}")]
+    InvalidDimensions { width: usize, height: usize },
+    #[error("invalid archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("invalid tileset image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("invalid tileset: {0}")]
+    Tileset(#[from] TilesetError),
+    #[error("file is truncated")]
+    Truncated,
+    #[error("file header is invalid")]
+    InvalidHeader,
+    #[error("invalid {0}")]
+    Invalid(&'static str),
+    #[error("unsupported {0}")]
+    Unsupported(&'static str),
+    #[error("invalid XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("invalid font: {0}")]
+    Font(#[from] FontError),
+    #[error("expected {expected} {field} entries, found {found}")]
+    LengthMismatch {
+        field: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("file has no path to save to")]
+    NoHandle,
+    #[error("couldn't write file: {0}")]
+    Io(#[from] io::Error),
+    #[error("couldn't encode file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("couldn't write archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("couldn't encode tileset image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("document can't be saved in this format: {0}")]
+    Unrepresentable(&'static str),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SaveData {
+    pub version: u32,
     pub width: usize,
     pub height: usize,
     pub tiles: Vec<u32>,
     pub foreground: Vec<u32>,
     pub background: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sauce: Option<Sauce>,
 }
 
 impl SaveData {
+    pub fn from_canvas(canvas: &Canvas) -> SaveData {
+        let (width, height) = canvas.size();
+        let mut tiles = vec![];
+        let mut foreground = vec![];
+        let mut background = vec![];
+        for (_, _, tile) in canvas.tiles() {
+            tiles.push(tile.index);
+            foreground.push(tile.fg.to_argb());
+            background.push(tile.bg.to_argb());
+        }
+        SaveData {
+            version: FORMAT_VERSION,
+            width: width as usize,
+            height: height as usize,
+            tiles,
+            foreground,
+            background,
+            sauce: None,
+        }
+    }
+
+    /// Save data for a document, including its metadata.
+    pub fn from_state(state: &FileState) -> SaveData {
+        SaveData {
+            sauce: state.sauce.clone(),
+            ..SaveData::from_canvas(&state.canvas)
+        }
+    }
+
+    /// Reads save data of any supported version, upgrading it to the current layout.
+    pub fn from_reader(reader: impl Read) -> Result<SaveData, LoadError> {
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+        let value = migration::migrate(value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), SaveError> {
+        Ok(serde_json::to_writer(writer, self)?)
+    }
+
+    pub fn to_canvas(&self) -> Result<Canvas, LoadError> {
+        let (width, height) = self.validate()?;
+        let mut canvas = Canvas::new(width, height);
+        canvas.set_all_tiles(self.tiles());
+        Ok(canvas)
+    }
+
+    /// Checks that the dimensions fit a `Canvas` and that every array covers the whole canvas.
+    fn validate(&self) -> Result<(u32, u32), LoadError> {
+        let invalid_dimensions = || LoadError::InvalidDimensions {
+            width: self.width,
+            height: self.height,
+        };
+        let width: u32 = self.width.try_into().map_err(|_| invalid_dimensions())?;
+        let height: u32 = self.height.try_into().map_err(|_| invalid_dimensions())?;
+        let expected = self
+            .width
+            .checked_mul(self.height)
+            .filter(|&len| len > 0)
+            .ok_or_else(invalid_dimensions)?;
+        for (field, found) in [
+            ("tiles", self.tiles.len()),
+            ("foreground", self.foreground.len()),
+            ("background", self.background.len()),
+        ] {
+            if found != expected {
+                return Err(LoadError::LengthMismatch {
+                    field,
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok((width, height))
+    }
+
     fn tiles(&self) -> Vec<Tile> {
         let mut tiles = vec![];
         for ((&index, &fg), &bg) in self
@@ -61,45 +254,129 @@ impl SaveData {
     }
 }
 
-pub fn save(state: &FileState) -> io::Result<()> {
-    let handle = state
-        .handle
-        .as_ref()
-        .expect("FileState file handle missing!");
-    let (width, height) = state.canvas.size();
-    let mut tiles = vec![];
-    let mut foreground = vec![];
-    let mut background = vec![];
-    for (_, _, tile) in state.canvas.tiles() {
-        tiles.push(tile.index);
-        foreground.push(tile.fg.to_argb());
-        background.push(tile.bg.to_argb());
+/// Saves the document to its handle's path. The file is encoded in full before the old one is
+/// replaced, so a document that can't be saved in the format leaves the file as it was.
+pub fn save(state: &FileState) -> Result<(), SaveError> {
+    let handle = state.handle.as_ref().ok_or(SaveError::NoHandle)?;
+    let mut file = Cursor::new(Vec::new());
+    match handle.file_type {
+        FileType::Monti => SaveData::from_state(state).to_writer(&mut file)?,
+        FileType::MontiBundle => {
+            bundle::save(&mut file, &SaveData::from_state(state), &state.tileset)?
+        }
+        FileType::Ansi => {
+            let options = AnsiOptions::from_sauce(state.sauce.as_ref());
+            let mut data = ansi::write(&state.canvas, options);
+            // Without a record, viewers assume the default width.
+            if state.sauce.is_some() || state.canvas.width != ansi::DEFAULT_WIDTH {
+                ansi::sauce(&state.canvas, options, state.sauce.as_ref()).append_to(&mut data);
+            }
+            file.write_all(&data)?;
+        }
+        FileType::XBin => {
+            let mut data = xbin::write(&state.canvas, &state.tileset)?;
+            if let Some(sauce) = &state.sauce {
+                xbin::sauce(&state.canvas, sauce).append_to(&mut data);
+            }
+            file.write_all(&data)?;
+        }
+        FileType::RexPaint => rexpaint::write(&state.canvas, &mut file)?,
+        FileType::TiledXml | FileType::TiledJson => {
+            let format = match handle.file_type {
+                FileType::TiledXml => MapFormat::Xml,
+                _ => MapFormat::Json,
+            };
+            tiled::save(
+                &mut file,
+                &handle.path,
+                format,
+                &state.canvas,
+                &state.tileset,
+            )?
+        }
     }
-    let save_data = SaveData {
-        width: width as usize,
-        height: height as usize,
-        tiles,
-        foreground,
-        background,
-    };
-    let file = File::create(&handle.path)?;
-    serde_json::to_writer(file, &save_data)?;
+    fs::write(&handle.path, file.into_inner())?;
     Ok(())
 }
 
-pub fn load(path: impl AsRef<Path>) -> io::Result<FileState> {
+pub fn load(path: impl AsRef<Path>) -> Result<FileState, LoadError> {
     let path = path.as_ref().to_owned();
-    let file = File::open(&path)?;
-    let save_data: SaveData = serde_json::from_reader(file)?;
-    // TODO: Replace .unwrap() with proper error return
-    let mut canvas = Canvas::new(save_data.width.try_into().unwrap(), save_data.height.try_into().unwrap());
-    canvas.set_all_tiles(save_data.tiles());
+    let file_type = FileType::from_path(&path);
+    let mut file = BufReader::new(File::open(&path)?);
+    let (canvas, tileset, sauce) = match file_type {
+        FileType::Monti => {
+            let data = SaveData::from_reader(file)?;
+            (data.to_canvas()?, Tileset::default(), data.sauce)
+        }
+        FileType::MontiBundle => {
+            let (data, tileset) = bundle::load(file)?;
+            (data.to_canvas()?, tileset, data.sauce)
+        }
+        FileType::Ansi => {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            let (contents, sauce) = Sauce::split(&data);
+            let options = AnsiOptions::from_sauce(sauce.as_ref());
+            (ansi::read(contents, options)?, Tileset::default(), sauce)
+        }
+        FileType::XBin => {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            let (contents, sauce) = Sauce::split(&data);
+            let (canvas, tileset) = xbin::read(contents)?;
+            (canvas, tileset.unwrap_or_default(), sauce)
+        }
+        FileType::RexPaint => (rexpaint::read(file)?, Tileset::default(), None),
+        FileType::TiledXml | FileType::TiledJson => {
+            let format = match file_type {
+                FileType::TiledXml => MapFormat::Xml,
+                _ => MapFormat::Json,
+            };
+            let (canvas, tileset) = tiled::load(file, &path, format)?;
+            (canvas, tileset.unwrap_or_default(), None)
+        }
+    };
     Ok(FileState {
         canvas,
-        handle: Some(Handle {
-            path,
-            file_type: FileType::Monti,
-        }),
+        handle: Some(Handle { path, file_type }),
         modified: false,
+        history: History::default(),
+        tileset,
+        sauce,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_save_keeps_existing_file() {
+        let path = std::env::temp_dir().join(format!("monotile-save-{}.xb", std::process::id()));
+        fs::write(&path, b"existing").unwrap();
+        // XBin only has room for 16 colours.
+        let mut canvas = Canvas::new(20, 1);
+        for x in 0..20 {
+            let fg = Color::rgb(x as u8, 0, 0);
+            canvas.set_tile(
+                x,
+                0,
+                Tile {
+                    fg,
+                    ..Tile::default()
+                },
+            );
+        }
+        let state = FileState {
+            handle: Some(Handle {
+                path: path.clone(),
+                file_type: FileType::XBin,
+            }),
+            canvas,
+            ..FileState::default()
+        };
+        assert!(matches!(save(&state), Err(SaveError::Unrepresentable(_))));
+        assert_eq!(fs::read(&path).unwrap(), b"existing");
+        fs::remove_file(path).unwrap();
+    }
+}
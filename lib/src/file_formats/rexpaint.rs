@@ -0,0 +1,152 @@
+//! REXPaint (`.xp`): gzip-compressed layers of glyph, foreground and background cells.
+//!
+//! Cells are stored column by column. A background of magenta (255, 0, 255) marks a transparent
+//! cell, through which the layers below show. Canvases only have one layer, so the layers are
+//! flattened on reading, and anything still transparent becomes the default tile.
+
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use super::{LoadError, SaveError};
+use crate::canvas::{Canvas, Color, Tile};
+
+/// Version number REXPaint writes at the start of its files.
+const VERSION: i32 = -1;
+const CELL_SIZE: usize = 10;
+
+/// Background colour REXPaint uses for transparent cells.
+pub const TRANSPARENT: Color = Color::rgb(255, 0, 255);
+
+/// Reads an `.xp` file, flattening its layers into one canvas the size of the first layer.
+pub fn read(reader: impl Read) -> Result<Canvas, LoadError> {
+    let mut data = Vec::new();
+    GzDecoder::new(reader).read_to_end(&mut data)?;
+    let mut rest = &data[..];
+
+    // Files from before REXPaint 1.02 start directly with the layer count.
+    let mut layers = read_i32(&mut rest)?;
+    if layers < 0 {
+        layers = read_i32(&mut rest)?;
+    }
+    if layers < 1 {
+        return Err(LoadError::InvalidHeader);
+    }
+
+    let mut canvas: Option<Canvas> = None;
+    for _ in 0..layers {
+        let (width, height) = (read_i32(&mut rest)?, read_i32(&mut rest)?);
+        let invalid_dimensions = || LoadError::InvalidDimensions {
+            width: width as usize,
+            height: height as usize,
+        };
+        let (Ok(width), Ok(height)) = (u32::try_from(width), u32::try_from(height)) else {
+            return Err(invalid_dimensions());
+        };
+        if width == 0 || height == 0 {
+            return Err(invalid_dimensions());
+        }
+        let cells = take(
+            &mut rest,
+            (width as usize * height as usize).saturating_mul(CELL_SIZE),
+        )?;
+        let canvas = canvas.get_or_insert_with(|| Canvas::new(width, height));
+        for (i, cell) in cells.chunks_exact(CELL_SIZE).enumerate() {
+            let (x, y) = (i as u32 / height, i as u32 % height);
+            let bg = Color::rgb(cell[7], cell[8], cell[9]);
+            if bg == TRANSPARENT || x >= canvas.width || y >= canvas.height {
+                continue;
+            }
+            let tile = Tile {
+                index: u32::from_le_bytes(cell[..4].try_into().unwrap()),
+                fg: Color::rgb(cell[4], cell[5], cell[6]),
+                bg,
+            };
+            canvas.set_tile(x, y, tile);
+        }
+    }
+    Ok(canvas.unwrap())
+}
+
+fn read_i32(rest: &mut &[u8]) -> Result<i32, LoadError> {
+    Ok(i32::from_le_bytes(take(rest, 4)?.try_into().unwrap()))
+}
+
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], LoadError> {
+    if rest.len() < len {
+        return Err(LoadError::Truncated);
+    }
+    let (taken, remaining) = rest.split_at(len);
+    *rest = remaining;
+    Ok(taken)
+}
+
+/// Writes a canvas as a single layer `.xp` file.
+///
+/// Tiles with a magenta background are written as they are, so REXPaint shows them as
+/// transparent.
+pub fn write(canvas: &Canvas, writer: impl Write) -> Result<(), SaveError> {
+    let (width, height) = canvas.size();
+    let (Ok(width), Ok(height)) = (i32::try_from(width), i32::try_from(height)) else {
+        return Err(SaveError::Unrepresentable("canvas is too large"));
+    };
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    for value in [VERSION, 1, width, height] {
+        encoder.write_all(&value.to_le_bytes())?;
+    }
+    for x in 0..canvas.width {
+        for y in 0..canvas.height {
+            let tile = canvas.get_tile(x, y);
+            encoder.write_all(&tile.index.to_le_bytes())?;
+            encoder.write_all(&[tile.fg.r, tile.fg.g, tile.fg.b])?;
+            encoder.write_all(&[tile.bg.r, tile.bg.g, tile.bg.b])?;
+        }
+    }
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_to_vec(canvas: &Canvas) -> Vec<u8> {
+        let mut data = Vec::new();
+        write(canvas, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trip_keeps_tiles() {
+        // Wider than high, so a mix-up between rows and columns shows.
+        let mut original = Canvas::new(5, 2);
+        let tiles = (0..10u8)
+            .map(|i| Tile {
+                index: 1000 + i as u32,
+                fg: Color::rgb(i, 2 * i, 3 * i),
+                bg: Color::rgb(200, i, 100),
+            })
+            .collect();
+        original.set_all_tiles(tiles);
+        let canvas = read(write_to_vec(&original).as_slice()).unwrap();
+        assert_eq!(canvas, original);
+    }
+
+    #[test]
+    fn transparent_cells_read_as_default_tiles() {
+        let mut original = Canvas::new(2, 1);
+        original.set_tile(
+            1,
+            0,
+            Tile {
+                index: 65,
+                bg: TRANSPARENT,
+                ..Tile::default()
+            },
+        );
+        let canvas = read(write_to_vec(&original).as_slice()).unwrap();
+        assert_eq!(canvas.get_tile(1, 0), Tile::default());
+    }
+}
@@ -0,0 +1,135 @@
+//! Canvases as SVG images drawn with the tileset's glyphs, so they look the same as in the editor
+//! at any size.
+//!
+//! Each glyph used is defined once as a path covering its pixels, and placed on top of rectangles
+//! for the backgrounds. Pixels count as part of the glyph when their coverage is at least half.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use image::GenericImageView;
+
+use crate::canvas::Canvas;
+use crate::tileset::Tileset;
+
+/// Outline of a glyph's pixels, merging each row's runs into single rectangles.
+fn glyph_path(tileset: &Tileset, index: u32) -> String {
+    let Some(glyph) = tileset.tile(index) else {
+        return String::new();
+    };
+    let (width, height) = glyph.dimensions();
+    let mut path = String::new();
+    for y in 0..height {
+        let mut x = 0;
+        while x < width {
+            if glyph.get_pixel(x, y).0[0] < 0x80 {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < width && glyph.get_pixel(x, y).0[0] >= 0x80 {
+                x += 1;
+            }
+            write!(path, "M{start} {y}h{}v1h-{}z", x - start, x - start).unwrap();
+        }
+    }
+    path
+}
+
+pub fn to_svg(canvas: &Canvas, tileset: &Tileset) -> String {
+    let (tile_width, tile_height) = tileset.tile_size;
+    let (width, height) = (canvas.width * tile_width, canvas.height * tile_height);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+         width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" \
+         shape-rendering=\"crispEdges\">\n"
+    );
+
+    let mut drawn = BTreeSet::new();
+    let used: BTreeSet<u32> = canvas.tiles().map(|(_, _, tile)| tile.index).collect();
+    svg.push_str("<defs>\n");
+    for index in used {
+        let path = glyph_path(tileset, index);
+        if !path.is_empty() {
+            writeln!(svg, "<path id=\"t{index}\" d=\"{path}\"/>").unwrap();
+            drawn.insert(index);
+        }
+    }
+    svg.push_str("</defs>\n");
+
+    // Backgrounds, merged along each row.
+    for y in 0..canvas.height {
+        let mut x = 0;
+        while x < canvas.width {
+            let bg = canvas.get_tile(x, y).bg;
+            let start = x;
+            while x < canvas.width && canvas.get_tile(x, y).bg == bg {
+                x += 1;
+            }
+            writeln!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{tile_height}\" fill=\"{}\"/>",
+                start * tile_width,
+                y * tile_height,
+                (x - start) * tile_width,
+                bg.to_hex()
+            )
+            .unwrap();
+        }
+    }
+
+    for (x, y, tile) in canvas.tiles() {
+        if !drawn.contains(&tile.index) {
+            continue;
+        }
+        writeln!(
+            svg,
+            "<use xlink:href=\"#t{}\" x=\"{}\" y=\"{}\" fill=\"{}\"/>",
+            tile.index,
+            x * tile_width,
+            y * tile_height,
+            tile.fg.to_hex()
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Color, Tile};
+
+    #[test]
+    fn glyphs_are_defined_once_over_merged_backgrounds() {
+        // A tileset of two 2x1 tiles, blank and a full row.
+        let image = image::GrayImage::from_raw(4, 1, vec![0, 0, 255, 255]).unwrap();
+        let tileset = Tileset::new(image, (2, 1)).unwrap();
+        let blue = Color::rgb(0, 0, 0xff);
+        let mut canvas = Canvas::new(3, 1);
+        let blank = Tile {
+            index: 0,
+            ..Tile::default()
+        };
+        canvas.set_all_tiles(vec![blank; 3]);
+        for x in [0, 2] {
+            let tile = Tile {
+                index: 1,
+                fg: blue,
+                ..canvas.get_tile(x, 0)
+            };
+            canvas.set_tile(x, 0, tile);
+        }
+        let svg = to_svg(&canvas, &tileset);
+        let body = svg.split_once('\n').unwrap().1;
+        assert!(svg.contains("width=\"6\" height=\"1\" viewBox=\"0 0 6 1\""));
+        assert_eq!(
+            body,
+            "<defs>\n<path id=\"t1\" d=\"M0 0h2v1h-2z\"/>\n</defs>\n\
+             <rect x=\"0\" y=\"0\" width=\"6\" height=\"1\" fill=\"#000000\"/>\n\
+             <use xlink:href=\"#t1\" x=\"0\" y=\"0\" fill=\"#0000ff\"/>\n\
+             <use xlink:href=\"#t1\" x=\"4\" y=\"0\" fill=\"#0000ff\"/>\n</svg>\n"
+        );
+    }
+}
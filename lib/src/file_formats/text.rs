@@ -0,0 +1,142 @@
+//! Canvases as Unicode text, one line per row, using the tileset's character map.
+
+use std::fmt::Write;
+
+use crate::canvas::{Canvas, Color, Tile};
+use crate::tileset::Tileset;
+
+/// Character written for tiles the character map doesn't cover.
+pub const UNMAPPED: char = '?';
+const TAB_WIDTH: usize = 8;
+
+pub(crate) fn char_for(tileset: &Tileset, index: u32) -> char {
+    match tileset.char_for(index) {
+        Some(c) if !c.is_control() => c,
+        Some(_) => ' ',
+        None => UNMAPPED,
+    }
+}
+
+/// The tile for a character, falling back to the tile for [`UNMAPPED`], or the character's code
+/// point if the tileset has no character map at all.
+fn index_for(tileset: &Tileset, c: char) -> u32 {
+    if tileset.charmap.is_none() {
+        return (c as u32).min(tileset.tile_count() - 1);
+    }
+    tileset
+        .index_for(c)
+        .or_else(|| tileset.index_for(UNMAPPED))
+        .unwrap_or(0)
+}
+
+/// The canvas as plain text, without trailing spaces on each line.
+pub fn to_text(canvas: &Canvas, tileset: &Tileset) -> String {
+    let mut text = String::new();
+    for y in 0..canvas.height {
+        let line: String = (0..canvas.width)
+            .map(|x| char_for(tileset, canvas.get_tile(x, y).index))
+            .collect();
+        text.push_str(line.trim_end_matches(' '));
+        text.push('\n');
+    }
+    text
+}
+
+/// The canvas as text with 24-bit colour escape sequences, for showing in a terminal.
+///
+/// Colours are reset at the end of each line, so the background doesn't spill over to the rest
+/// of the terminal line.
+pub fn to_ansi_text(canvas: &Canvas, tileset: &Tileset) -> String {
+    let mut text = String::new();
+    for y in 0..canvas.height {
+        let mut current: Option<(Color, Color)> = None;
+        for x in 0..canvas.width {
+            let tile = canvas.get_tile(x, y);
+            let (fg, bg) = (tile.fg, tile.bg);
+            match current {
+                Some((current_fg, current_bg)) if current_fg == fg && current_bg == bg => {}
+                Some((current_fg, _)) if current_fg == fg => {
+                    write!(text, "\x1b[48;2;{};{};{}m", bg.r, bg.g, bg.b).unwrap();
+                }
+                Some((_, current_bg)) if current_bg == bg => {
+                    write!(text, "\x1b[38;2;{};{};{}m", fg.r, fg.g, fg.b).unwrap();
+                }
+                _ => write!(
+                    text,
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                    fg.r, fg.g, fg.b, bg.r, bg.g, bg.b
+                )
+                .unwrap(),
+            }
+            current = Some((fg, bg));
+            text.push(char_for(tileset, tile.index));
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    text
+}
+
+/// Makes a canvas from text, as wide as its longest line, with every tile in the given colours.
+///
+/// Tabs are expanded to the next multiple of 8 columns.
+pub fn from_text(text: &str, tileset: &Tileset, fg: Color, bg: Color) -> Canvas {
+    let lines: Vec<Vec<char>> = text
+        .lines()
+        .map(|line| {
+            let mut chars = Vec::new();
+            for c in line.chars() {
+                if c == '\t' {
+                    let next_stop = (chars.len() / TAB_WIDTH + 1) * TAB_WIDTH;
+                    chars.resize(next_stop, ' ');
+                } else {
+                    chars.push(c);
+                }
+            }
+            chars
+        })
+        .collect();
+    let width = lines.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let height = lines.len().max(1);
+    let blank = Tile {
+        index: index_for(tileset, ' '),
+        fg,
+        bg,
+    };
+    let mut tiles = vec![blank; width * height];
+    for (y, line) in lines.iter().enumerate() {
+        for (x, &c) in line.iter().enumerate() {
+            tiles[y * width + x].index = index_for(tileset, c);
+        }
+    }
+    let mut canvas = Canvas::new(width as u32, height as u32);
+    canvas.set_all_tiles(tiles);
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::charmap::CharMap;
+
+    #[test]
+    fn text_round_trips_through_the_charmap() {
+        let tileset = Tileset::default().with_charmap(CharMap::cp437());
+        let (fg, bg) = (Color::rgb(1, 2, 3), Color::rgb(4, 5, 6));
+        let canvas = from_text("Hé ☺\n\tA\n", &tileset, fg, bg);
+        assert_eq!(canvas.size(), (9, 2));
+        let indices: Vec<u32> = (0..4).map(|x| canvas.get_tile(x, 0).index).collect();
+        assert_eq!(indices, [b'H' as u32, 0x82, b' ' as u32, 1]);
+        assert!(canvas
+            .tiles()
+            .all(|(_, _, tile)| tile.fg == fg && tile.bg == bg));
+        assert_eq!(to_text(&canvas, &tileset), "Hé ☺\n        A\n");
+    }
+
+    #[test]
+    fn unmapped_characters_become_the_fallback() {
+        let tileset = Tileset::default();
+        let canvas = from_text("a€", &tileset, Color::default(), Color::default());
+        assert_eq!(canvas.get_tile(1, 0).index, UNMAPPED as u32);
+        assert_eq!(to_text(&canvas, &tileset), "a?\n");
+    }
+}
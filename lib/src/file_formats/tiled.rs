@@ -0,0 +1,483 @@
+//! Tiled maps, as XML (`.tmx`) or JSON (`.tmj`).
+//!
+//! A map is written with one tileset, saved as a PNG image next to the map, and one tile layer.
+//! Tiled numbers tiles from 1 with 0 meaning no tile, so a tile's id is its index plus one. Tiled
+//! has no per-cell colours, so they are kept as layer properties holding comma-separated
+//! `#rrggbb` lists in the same order as the layer data.
+//!
+//! Reading supports the subset written here: the first tile layer, in CSV or XML encoding for
+//! `.tmx` files, and the first tileset if its image is embedded in the map.
+
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::html::escape;
+use super::{LoadError, SaveError};
+use crate::canvas::{Canvas, Color, Tile};
+use crate::tileset::{Coverage, Tileset};
+
+const MAP_VERSION: &str = "1.10";
+const LAYER_NAME: &str = "Tiles";
+const FOREGROUND_PROPERTY: &str = "foreground";
+const BACKGROUND_PROPERTY: &str = "background";
+/// Flags Tiled keeps in the top bits of tile ids for flipped and rotated tiles.
+const FLIP_FLAGS: u32 = 0xF000_0000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapFormat {
+    Xml,
+    Json,
+}
+
+/// Where the tileset image of a map is saved, next to the map itself.
+pub fn tileset_image_path(map_path: &Path) -> PathBuf {
+    let stem = map_path.file_stem().unwrap_or_default().to_string_lossy();
+    map_path.with_file_name(format!("{stem}-tileset.png"))
+}
+
+/// Writes the map, and its tileset image next to where the map is saved.
+pub fn save(
+    mut writer: impl Write,
+    path: &Path,
+    format: MapFormat,
+    canvas: &Canvas,
+    tileset: &Tileset,
+) -> Result<(), SaveError> {
+    // Ids past the tileset would be drawn as nothing, and the largest would run into the flip
+    // flags or overflow.
+    if canvas
+        .tiles()
+        .any(|(_, _, tile)| tile.index >= tileset.tile_count())
+    {
+        return Err(SaveError::Unrepresentable(
+            "Tiled maps can only use tiles from the tileset",
+        ));
+    }
+    let image_path = tileset_image_path(path);
+    // Coverage becomes alpha, so Tiled can tint the white glyphs.
+    let (image_width, image_height) = tileset.image.dimensions();
+    let image = RgbaImage::from_fn(image_width, image_height, |x, y| {
+        Rgba([
+            u8::MAX,
+            u8::MAX,
+            u8::MAX,
+            tileset.image.get_pixel(x, y).0[0],
+        ])
+    });
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageFormat::Png)?;
+    let image_name = image_path.file_name().unwrap().to_string_lossy();
+    let map = match format {
+        MapFormat::Xml => write_tmx(canvas, tileset, &image_name),
+        MapFormat::Json => write_json(canvas, tileset, &image_name)?,
+    };
+    // Only touch the files on disk once everything has been encoded.
+    fs::write(&image_path, png.into_inner())?;
+    writer.write_all(map.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn colors(canvas: &Canvas, color: fn(&Tile) -> Color) -> String {
+    let colors: Vec<String> = canvas
+        .tiles()
+        .map(|(_, _, tile)| color(tile).to_hex())
+        .collect();
+    colors.join(",")
+}
+
+fn write_tmx(canvas: &Canvas, tileset: &Tileset, image: &str) -> String {
+    let (width, height) = canvas.size();
+    let (tile_width, tile_height) = tileset.tile_size;
+    let (image_width, image_height) = tileset.image.dimensions();
+    let mut data = String::new();
+    for y in 0..height {
+        let row: Vec<String> = (0..width)
+            .map(|x| (canvas.get_tile(x, y).index + 1).to_string())
+            .collect();
+        data.push_str(&row.join(","));
+        data.push_str(if y + 1 < height { ",\n" } else { "\n" });
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="{MAP_VERSION}" orientation="orthogonal" renderorder="right-down" width="{width}" height="{height}" tilewidth="{tile_width}" tileheight="{tile_height}" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="monotile" tilewidth="{tile_width}" tileheight="{tile_height}" tilecount="{tile_count}" columns="{columns}">
+  <image source="{image}" width="{image_width}" height="{image_height}"/>
+ </tileset>
+ <layer id="1" name="{LAYER_NAME}" width="{width}" height="{height}">
+  <properties>
+   <property name="{FOREGROUND_PROPERTY}" value="{foreground}"/>
+   <property name="{BACKGROUND_PROPERTY}" value="{background}"/>
+  </properties>
+  <data encoding="csv">
+{data}</data>
+ </layer>
+</map>
+"#,
+        tile_count = tileset.tile_count(),
+        image = escape(image),
+        columns = tileset.width(),
+        foreground = colors(canvas, |tile| tile.fg),
+        background = colors(canvas, |tile| tile.bg),
+    )
+}
+
+fn write_json(canvas: &Canvas, tileset: &Tileset, image: &str) -> Result<String, SaveError> {
+    let (width, height) = canvas.size();
+    let (tile_width, tile_height) = tileset.tile_size;
+    let data: Vec<u32> = canvas.tiles().map(|(_, _, tile)| tile.index + 1).collect();
+    let map = json!({
+        "type": "map",
+        "version": MAP_VERSION,
+        "orientation": "orthogonal",
+        "renderorder": "right-down",
+        "width": width,
+        "height": height,
+        "tilewidth": tile_width,
+        "tileheight": tile_height,
+        "infinite": false,
+        "nextlayerid": 2,
+        "nextobjectid": 1,
+        "tilesets": [{
+            "firstgid": 1,
+            "name": "monotile",
+            "image": image,
+            "imagewidth": tileset.image.width(),
+            "imageheight": tileset.image.height(),
+            "tilewidth": tile_width,
+            "tileheight": tile_height,
+            "tilecount": tileset.tile_count(),
+            "columns": tileset.width(),
+            "margin": 0,
+            "spacing": 0,
+        }],
+        "layers": [{
+            "id": 1,
+            "name": LAYER_NAME,
+            "type": "tilelayer",
+            "x": 0,
+            "y": 0,
+            "width": width,
+            "height": height,
+            "opacity": 1,
+            "visible": true,
+            "data": data,
+            "properties": [
+                {
+                    "name": FOREGROUND_PROPERTY,
+                    "type": "string",
+                    "value": colors(canvas, |tile| tile.fg),
+                },
+                {
+                    "name": BACKGROUND_PROPERTY,
+                    "type": "string",
+                    "value": colors(canvas, |tile| tile.bg),
+                },
+            ],
+        }],
+    });
+    Ok(serde_json::to_string_pretty(&map)?)
+}
+
+/// The parts of a map that are read, in the layout of Tiled's JSON format.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Map {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    tilesets: Vec<TilesetRef>,
+    layers: Vec<Layer>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TilesetRef {
+    firstgid: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    image: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Layer {
+    #[serde(rename = "type")]
+    kind: String,
+    data: Vec<u32>,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Property {
+    name: String,
+    value: serde_json::Value,
+}
+
+/// Reads a map from `path`, with the tileset image it references if there is one.
+pub fn load(
+    mut reader: impl Read,
+    path: &Path,
+    format: MapFormat,
+) -> Result<(Canvas, Option<Tileset>), LoadError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let map = match format {
+        MapFormat::Xml => parse_tmx(&text)?,
+        MapFormat::Json => serde_json::from_str(&text)?,
+    };
+    let invalid_dimensions = || LoadError::InvalidDimensions {
+        width: map.width as usize,
+        height: map.height as usize,
+    };
+    let cells = (map.width as usize)
+        .checked_mul(map.height as usize)
+        .filter(|&cells| cells > 0)
+        .ok_or_else(invalid_dimensions)?;
+    let layer = map
+        .layers
+        .iter()
+        .find(|layer| layer.kind == "tilelayer")
+        .ok_or(LoadError::Unsupported("map without a tile layer"))?;
+    if layer.data.len() != cells {
+        return Err(LoadError::LengthMismatch {
+            field: "tile",
+            expected: cells,
+            found: layer.data.len(),
+        });
+    }
+    let first_tileset = map.tilesets.first();
+    let first_gid = first_tileset.map_or(1, |tileset| tileset.firstgid.max(1));
+    let property_colors = |name: &str| -> Result<Option<Vec<Color>>, LoadError> {
+        let Some(value) = layer
+            .properties
+            .iter()
+            .find(|property| property.name == name)
+            .and_then(|property| property.value.as_str())
+        else {
+            return Ok(None);
+        };
+        let colors: Vec<Color> = value.split(',').filter_map(parse_color).collect();
+        if colors.len() != cells {
+            return Err(LoadError::LengthMismatch {
+                field: "colour",
+                expected: cells,
+                found: colors.len(),
+            });
+        }
+        Ok(Some(colors))
+    };
+    let foreground = property_colors(FOREGROUND_PROPERTY)?;
+    let background = property_colors(BACKGROUND_PROPERTY)?;
+
+    let default = Tile::default();
+    let tiles = layer
+        .data
+        .iter()
+        .enumerate()
+        .map(|(i, &gid)| Tile {
+            // Empty cells become the default tile.
+            index: (gid & !FLIP_FLAGS)
+                .checked_sub(first_gid)
+                .unwrap_or(default.index),
+            fg: foreground.as_ref().map_or(default.fg, |colors| colors[i]),
+            bg: background.as_ref().map_or(default.bg, |colors| colors[i]),
+        })
+        .collect();
+    let mut canvas = Canvas::new(map.width, map.height);
+    canvas.set_all_tiles(tiles);
+
+    let tileset = match first_tileset {
+        Some(TilesetRef {
+            image: Some(image),
+            tilewidth,
+            tileheight,
+            ..
+        }) => {
+            let tile_size = match (tilewidth, tileheight) {
+                (0, 0) => (map.tilewidth, map.tileheight),
+                size => (*size.0, *size.1),
+            };
+            let image_path = path.with_file_name(image);
+            Some(Tileset::from_path(image_path, tile_size, Coverage::Alpha)?)
+        }
+        _ => None,
+    };
+    Ok((canvas, tileset))
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    let hex = text.trim().strip_prefix('#')?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    // Tiled writes colours with alpha as #aarrggbb.
+    Some(Color::rgb(
+        (value >> 16 & 0xFF) as u8,
+        (value >> 8 & 0xFF) as u8,
+        (value & 0xFF) as u8,
+    ))
+}
+
+/// Reads the same parts of a `.tmx` file as the JSON format's [`Map`].
+fn parse_tmx(text: &str) -> Result<Map, LoadError> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+    let mut map = Map::default();
+    // The innermost elements that matter, since `<image>` and `<properties>` appear in several.
+    let mut in_tileset = false;
+    let mut in_layer = false;
+    let mut data_encoding: Option<String> = None;
+    loop {
+        let event = reader.read_event()?;
+        let (element, is_empty) = match &event {
+            Event::Start(element) => (Some(element), false),
+            Event::Empty(element) => (Some(element), true),
+            _ => (None, false),
+        };
+        if let Some(element) = element {
+            match element.name().as_ref() {
+                b"map" => {
+                    map.width = number(element, "width")?;
+                    map.height = number(element, "height")?;
+                    map.tilewidth = number(element, "tilewidth")?;
+                    map.tileheight = number(element, "tileheight")?;
+                }
+                b"tileset" => {
+                    map.tilesets.push(TilesetRef {
+                        firstgid: number(element, "firstgid")?,
+                        tilewidth: number(element, "tilewidth")?,
+                        tileheight: number(element, "tileheight")?,
+                        image: None,
+                    });
+                    in_tileset = !is_empty;
+                }
+                b"image" if in_tileset => {
+                    if let Some(tileset) = map.tilesets.last_mut() {
+                        tileset.image = attribute(element, "source")?;
+                    }
+                }
+                b"layer" => {
+                    map.layers.push(Layer {
+                        kind: "tilelayer".into(),
+                        ..Layer::default()
+                    });
+                    in_layer = !is_empty;
+                }
+                b"property" if in_layer => {
+                    if let Some(layer) = map.layers.last_mut() {
+                        layer.properties.push(Property {
+                            name: attribute(element, "name")?.unwrap_or_default(),
+                            value: attribute(element, "value")?.unwrap_or_default().into(),
+                        });
+                    }
+                }
+                b"data" if in_layer => {
+                    let encoding = attribute(element, "encoding")?.unwrap_or_default();
+                    if !encoding.is_empty() && encoding != "csv" {
+                        return Err(LoadError::Unsupported("layer data encoding"));
+                    }
+                    data_encoding = (!is_empty).then_some(encoding);
+                }
+                b"tile" if data_encoding.is_some() => {
+                    if let Some(layer) = map.layers.last_mut() {
+                        layer.data.push(number(element, "gid")?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        match event {
+            Event::Text(text) if data_encoding.as_deref() == Some("csv") => {
+                let text = text.unescape()?;
+                if let Some(layer) = map.layers.last_mut() {
+                    for gid in text.split(',').map(str::trim).filter(|gid| !gid.is_empty()) {
+                        let gid = gid.parse().map_err(|_| LoadError::Invalid("tile id"))?;
+                        layer.data.push(gid);
+                    }
+                }
+            }
+            Event::End(element) => match element.name().as_ref() {
+                b"tileset" => in_tileset = false,
+                b"layer" => in_layer = false,
+                b"data" => data_encoding = None,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(map)
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, LoadError> {
+    match element.try_get_attribute(name)? {
+        Some(attribute) => Ok(Some(attribute.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+/// A numeric attribute, 0 if it is missing.
+fn number(element: &BytesStart, name: &str) -> Result<u32, LoadError> {
+    match attribute(element, name)? {
+        Some(value) => value.parse().map_err(|_| LoadError::Invalid("number")),
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_tiles_and_tileset() {
+        let tileset = Tileset::default();
+        let mut original = Canvas::new(4, 3);
+        let tiles = (0..12u8)
+            .map(|i| Tile {
+                index: 32 + i as u32 * 7,
+                fg: Color::rgb(i * 20, 255 - i, 3),
+                bg: Color::rgb(0, i, i * 10),
+            })
+            .collect();
+        original.set_all_tiles(tiles);
+        for (format, extension) in [(MapFormat::Xml, "tmx"), (MapFormat::Json, "tmj")] {
+            let name = format!("monotile-tiled-{}.{extension}", std::process::id());
+            let path = std::env::temp_dir().join(name);
+            let mut map = Vec::new();
+            save(&mut map, &path, format, &original, &tileset).unwrap();
+            let (canvas, loaded) = load(map.as_slice(), &path, format).unwrap();
+            fs::remove_file(tileset_image_path(&path)).unwrap();
+            assert_eq!(canvas, original, "{format:?}");
+            let loaded = loaded.unwrap();
+            assert_eq!(loaded.tile_size, tileset.tile_size, "{format:?}");
+            assert_eq!(loaded.image, tileset.image, "{format:?}");
+        }
+    }
+    #[test]
+    fn tiles_outside_the_tileset_are_rejected() {
+        let tileset = Tileset::default();
+        for index in [tileset.tile_count(), 0x1000_0000, u32::MAX] {
+            let mut canvas = Canvas::new(1, 1);
+            canvas.set_tile(
+                0,
+                0,
+                Tile {
+                    index,
+                    ..Tile::default()
+                },
+            );
+            let path = std::env::temp_dir().join(format!("monotile-tiled-{index}.tmx"));
+            let result = save(Vec::new(), &path, MapFormat::Xml, &canvas, &tileset);
+            assert!(matches!(result, Err(SaveError::Unrepresentable(_))));
+            assert!(!tileset_image_path(&path).exists());
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! XBin (`.xb`): a binary text-mode format with the canvas size, an optional 16-colour palette,
+//! an optional 8-pixel wide bitmap font and run-length compressed character/attribute pairs.
+//!
+//! Attributes hold the foreground colour in the low nibble and the background colour in the high
+//! nibble, both indices into the palette. In 512 character mode the top bit of the foreground
+//! picks the upper 256 characters, leaving 8 foreground colours.
+
+use image::GenericImageView;
+
+use super::{ansi, LoadError, SaveError};
+use crate::canvas::{Canvas, Color, Tile};
+use crate::charmap::CharMap;
+use crate::font::Font;
+use crate::sauce::{self, Sauce};
+use crate::tileset::Tileset;
+
+const MAGIC: &[u8; 5] = b"XBIN\x1a";
+const HEADER_SIZE: usize = 11;
+const PALETTE_SIZE: usize = 48;
+const MAX_RUN: usize = 64;
+/// Font height viewers use for files without a font.
+const DEFAULT_FONT_HEIGHT: u8 = 16;
+
+const FLAG_PALETTE: u8 = 0x01;
+const FLAG_FONT: u8 = 0x02;
+const FLAG_COMPRESS: u8 = 0x04;
+const FLAG_NON_BLINK: u8 = 0x08;
+const FLAG_512_CHARS: u8 = 0x10;
+
+const RUN_LITERAL: u8 = 0x00;
+const RUN_CHAR: u8 = 0x40;
+const RUN_ATTR: u8 = 0x80;
+const RUN_BOTH: u8 = 0xC0;
+
+/// The VGA palette in the order of PC attribute bytes, used when a file has no palette.
+fn default_palette() -> [Color; 16] {
+    const ANSI_ORDER: [usize; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+    std::array::from_fn(|i| ansi::PALETTE[ANSI_ORDER[i % 8] + i / 8 * 8])
+}
+
+/// Reads an XBin file, along with the tileset built from its font if it has one.
+///
+/// Anything after the image data, like a SAUCE record, is ignored.
+pub fn read(data: &[u8]) -> Result<(Canvas, Option<Tileset>), LoadError> {
+    let header = data.get(..HEADER_SIZE).ok_or(LoadError::Truncated)?;
+    if !header.starts_with(MAGIC) {
+        return Err(LoadError::InvalidHeader);
+    }
+    let width = u16::from_le_bytes([header[5], header[6]]) as u32;
+    let height = u16::from_le_bytes([header[7], header[8]]) as u32;
+    let font_height = header[9] as u32;
+    let flags = header[10];
+    if width == 0 || height == 0 {
+        return Err(LoadError::InvalidDimensions {
+            width: width as usize,
+            height: height as usize,
+        });
+    }
+    let mut rest = &data[HEADER_SIZE..];
+    let mut take = |len: usize| {
+        if rest.len() < len {
+            return Err(LoadError::Truncated);
+        }
+        let (taken, remaining) = rest.split_at(len);
+        rest = remaining;
+        Ok(taken)
+    };
+
+    let mut palette = default_palette();
+    if flags & FLAG_PALETTE != 0 {
+        for (color, rgb) in palette.iter_mut().zip(take(PALETTE_SIZE)?.chunks_exact(3)) {
+            let channel = |value: u8| {
+                let value = value & 0x3F;
+                value << 2 | value >> 4
+            };
+            *color = Color::rgb(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]));
+        }
+    }
+
+    let chars_512 = flags & FLAG_512_CHARS != 0;
+    let tileset = if flags & FLAG_FONT != 0 {
+        let count = if chars_512 { 512 } else { 256 };
+        let bitmaps = take(count * font_height as usize)?;
+        let glyphs = bitmaps
+            .chunks_exact(font_height.max(1) as usize)
+            .map(|bitmap| {
+                bitmap
+                    .iter()
+                    .flat_map(|row| (0..8).map(move |x| row & (0x80 >> x)))
+                    .map(|set| if set != 0 { u8::MAX } else { 0 })
+                    .collect()
+            })
+            .collect();
+        let font = Font {
+            glyph_size: (8, font_height),
+            glyphs,
+            // XBin fonts replace the glyphs of the PC character set.
+            charmap: (!chars_512).then(CharMap::cp437),
+        };
+        Some(font.into_tileset()?)
+    } else {
+        None
+    };
+
+    let cells = (width * height) as usize;
+    // The size comes from the header, so the pairs are only collected as the data turns out to
+    // hold them.
+    let pairs = if flags & FLAG_COMPRESS != 0 {
+        let mut pairs = Vec::new();
+        while pairs.len() < cells {
+            let run = take(1)?[0];
+            let count = (run & 0x3F) as usize + 1;
+            match run & 0xC0 {
+                RUN_LITERAL => {
+                    let data = take(count * 2)?;
+                    pairs.extend(data.chunks_exact(2).map(|pair| (pair[0], pair[1])));
+                }
+                RUN_CHAR => {
+                    let char = take(1)?[0];
+                    pairs.extend(take(count)?.iter().map(|&attr| (char, attr)));
+                }
+                RUN_ATTR => {
+                    let attr = take(1)?[0];
+                    pairs.extend(take(count)?.iter().map(|&char| (char, attr)));
+                }
+                _ => {
+                    let pair = take(2)?;
+                    pairs.extend(std::iter::repeat_n((pair[0], pair[1]), count));
+                }
+            }
+        }
+        pairs.truncate(cells);
+        pairs
+    } else {
+        let data = take(cells * 2)?;
+        data.chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect()
+    };
+
+    let tiles = pairs
+        .into_iter()
+        .map(|(char, attr)| {
+            let mut index = char as u32;
+            let mut fg = attr & 0x0F;
+            if chars_512 && fg & 0x08 != 0 {
+                index += 256;
+                fg &= 0x07;
+            }
+            Tile {
+                index,
+                fg: palette[fg as usize],
+                // Blinking isn't shown, so the background is always one of all 16 colours.
+                bg: palette[(attr >> 4) as usize],
+            }
+        })
+        .collect();
+    let mut canvas = Canvas::new(width, height);
+    canvas.set_all_tiles(tiles);
+    Ok((canvas, tileset))
+}
+
+/// Writes a canvas as a compressed XBin file, with the palette it uses and the tileset as its
+/// font if the tiles are 8 pixels wide.
+pub fn write(canvas: &Canvas, tileset: &Tileset) -> Result<Vec<u8>, SaveError> {
+    let (width, height) = canvas.size();
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(SaveError::Unrepresentable(
+            "XBin canvases are at most 65535 tiles wide and high",
+        ));
+    };
+    let max_index = canvas
+        .tiles()
+        .map(|(_, _, tile)| tile.index)
+        .max()
+        .unwrap_or(0);
+    let chars_512 = match max_index {
+        0..=255 => false,
+        256..=511 => true,
+        _ => {
+            return Err(SaveError::Unrepresentable(
+                "XBin files have at most 512 characters",
+            ))
+        }
+    };
+    let palette = palette(canvas, chars_512)?;
+    let font = font(tileset, if chars_512 { 512 } else { 256 });
+
+    let mut flags = FLAG_PALETTE | FLAG_COMPRESS | FLAG_NON_BLINK;
+    if chars_512 {
+        flags |= FLAG_512_CHARS;
+    }
+    let font_height = match &font {
+        Some((font_height, _)) => {
+            flags |= FLAG_FONT;
+            *font_height
+        }
+        None => DEFAULT_FONT_HEIGHT,
+    };
+    let mut data = Vec::from(&MAGIC[..]);
+    data.extend_from_slice(&width.to_le_bytes());
+    data.extend_from_slice(&height.to_le_bytes());
+    data.push(font_height);
+    data.push(flags);
+    for color in &palette {
+        data.extend_from_slice(&[color.r >> 2, color.g >> 2, color.b >> 2]);
+    }
+    data.extend(std::iter::repeat_n(0, (16 - palette.len()) * 3));
+    if let Some((_, bitmaps)) = font {
+        data.extend(bitmaps);
+    }
+
+    let color_index = |color: Color| palette.iter().position(|&c| c == color).unwrap() as u8;
+    for y in 0..canvas.height {
+        let row: Vec<(u8, u8)> = (0..canvas.width)
+            .map(|x| {
+                let tile = canvas.get_tile(x, y);
+                let mut fg = color_index(tile.fg);
+                if tile.index > 255 {
+                    fg |= 0x08;
+                }
+                (tile.index as u8, color_index(tile.bg) << 4 | fg)
+            })
+            .collect();
+        compress_row(&row, &mut data);
+    }
+    Ok(data)
+}
+
+/// The SAUCE record describing a canvas written by [`write`], keeping the title, author and other
+/// details from an existing record.
+pub fn sauce(canvas: &Canvas, existing: &Sauce) -> Sauce {
+    let (width, height) = canvas.size();
+    Sauce {
+        data_type: sauce::DATA_TYPE_XBIN,
+        file_type: 0,
+        info: [
+            width.try_into().unwrap_or(u16::MAX),
+            height.try_into().unwrap_or(u16::MAX),
+            0,
+            0,
+        ],
+        // `write` always uses iCE colours.
+        flags: sauce::FLAG_NON_BLINK,
+        ..existing.clone()
+    }
+}
+
+/// Collects the colours used by the canvas. In 512 character mode the foreground colours have to
+/// come first, since only the first 8 entries can be used for them.
+fn palette(canvas: &Canvas, chars_512: bool) -> Result<Vec<Color>, SaveError> {
+    let mut palette = Vec::new();
+    let mut add = |color: Color| {
+        if !palette.contains(&color) {
+            palette.push(color);
+        }
+    };
+    if chars_512 {
+        canvas.tiles().for_each(|(_, _, tile)| add(tile.fg));
+        canvas.tiles().for_each(|(_, _, tile)| add(tile.bg));
+    } else {
+        canvas.tiles().for_each(|(_, _, tile)| {
+            add(tile.fg);
+            add(tile.bg);
+        });
+    }
+    let foreground_count = canvas
+        .tiles()
+        .map(|(_, _, tile)| palette.iter().position(|&c| c == tile.fg).unwrap())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    if palette.len() > 16 {
+        return Err(SaveError::Unrepresentable(
+            "XBin files have at most 16 colours",
+        ));
+    }
+    if chars_512 && foreground_count > 8 {
+        return Err(SaveError::Unrepresentable(
+            "XBin files with 512 characters have at most 8 foreground colours",
+        ));
+    }
+    Ok(palette)
+}
+
+/// Packs the tileset into an XBin font, if its tiles are 8 pixels wide and at most 32 high.
+/// Pixels with at least half coverage are set.
+fn font(tileset: &Tileset, count: u32) -> Option<(u8, Vec<u8>)> {
+    let (tile_width, tile_height) = tileset.tile_size;
+    if tile_width != 8 || !(1..=32).contains(&tile_height) {
+        return None;
+    }
+    let mut bitmaps = Vec::with_capacity((count * tile_height) as usize);
+    for index in 0..count {
+        match tileset.tile(index) {
+            Some(glyph) => {
+                for y in 0..tile_height {
+                    let row = (0..8).fold(0u8, |row, x| {
+                        let set = glyph.get_pixel(x, y).0[0] >= 0x80;
+                        row | if set { 0x80 >> x } else { 0 }
+                    });
+                    bitmaps.push(row);
+                }
+            }
+            None => bitmaps.extend(std::iter::repeat_n(0, tile_height as usize)),
+        }
+    }
+    Some((tile_height as u8, bitmaps))
+}
+
+/// Compresses one row of character/attribute pairs. Runs don't continue across rows.
+fn compress_row(row: &[(u8, u8)], out: &mut Vec<u8>) {
+    let run_length = |start: usize, same: &dyn Fn(&(u8, u8)) -> bool| {
+        row[start..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|pair| same(pair))
+            .count()
+    };
+    let mut i = 0;
+    while i < row.len() {
+        let (char, attr) = row[i];
+        let both = run_length(i, &|&pair| pair == (char, attr));
+        let chars = run_length(i, &|&(c, _)| c == char);
+        let attrs = run_length(i, &|&(_, a)| a == attr);
+        if both >= 2 && both >= chars.max(attrs) / 2 {
+            out.extend_from_slice(&[RUN_BOTH | (both - 1) as u8, char, attr]);
+            i += both;
+        } else if chars >= 2 && chars >= attrs {
+            out.extend_from_slice(&[RUN_CHAR | (chars - 1) as u8, char]);
+            out.extend(row[i..i + chars].iter().map(|&(_, a)| a));
+            i += chars;
+        } else if attrs >= 2 {
+            out.extend_from_slice(&[RUN_ATTR | (attrs - 1) as u8, attr]);
+            out.extend(row[i..i + attrs].iter().map(|&(c, _)| c));
+            i += attrs;
+        } else {
+            // Pairs that share nothing with their neighbour, up to where a run starts.
+            let mut end = i + 1;
+            while end < row.len()
+                && end - i < MAX_RUN
+                && (end + 1 == row.len()
+                    || (row[end].0 != row[end + 1].0 && row[end].1 != row[end + 1].1))
+            {
+                end += 1;
+            }
+            out.push(RUN_LITERAL | (end - i - 1) as u8);
+            for &(char, attr) in &row[i..end] {
+                out.extend_from_slice(&[char, attr]);
+            }
+            i = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_header_without_data_is_truncated() {
+        let mut data = Vec::from(&MAGIC[..]);
+        data.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 16]);
+        for flags in [0, FLAG_COMPRESS] {
+            let mut data = data.clone();
+            data.push(flags);
+            assert!(matches!(read(&data), Err(LoadError::Truncated)));
+        }
+    }
+
+    fn canvas(indices: &[u32], colors: &[Color]) -> Canvas {
+        let mut canvas = Canvas::new(8, 3);
+        let tiles = (0..24)
+            .map(|i| Tile {
+                index: indices[i % indices.len()],
+                fg: colors[i % colors.len()],
+                bg: colors[(i / 3) % colors.len()],
+            })
+            .collect();
+        canvas.set_all_tiles(tiles);
+        canvas
+    }
+
+    #[test]
+    fn round_trip_keeps_tiles_and_font() {
+        let tileset = Tileset::default();
+        // Runs of every kind, and colours that survive the 6-bit palette.
+        let indices = [65, 65, 65, 66, 67, 67, 32, 200];
+        let original = canvas(&indices, &ansi::PALETTE[..5]);
+        let (canvas, font) = read(&write(&original, &tileset).unwrap()).unwrap();
+        assert_eq!(canvas, original);
+
+        let font = font.unwrap();
+        assert_eq!(font.tile_size, tileset.tile_size);
+        let pixels = |tileset: &Tileset| -> Vec<bool> {
+            let glyph = tileset.tile(b'A' as u32).unwrap();
+            glyph
+                .pixels()
+                .map(|(_, _, pixel)| pixel.0[0] >= 0x80)
+                .collect()
+        };
+        assert_eq!(pixels(&font), pixels(&tileset));
+    }
+
+    #[test]
+    fn round_trip_with_512_characters() {
+        let original = canvas(&[1, 300, 511, 300], &ansi::PALETTE[8..]);
+        let (canvas, _) = read(&write(&original, &Tileset::default()).unwrap()).unwrap();
+        assert_eq!(canvas, original);
+    }
+}
@@ -1,8 +1,11 @@
-use std::{io, path::Path};
+use std::path::Path;
 
 use crate::{
     canvas::Canvas,
-    file_formats::{load, Handle},
+    file_formats::{load, Handle, LoadError},
+    history::History,
+    sauce::Sauce,
+    tileset::Tileset,
 };
 
 #[derive(Debug, Default)]
@@ -10,10 +13,14 @@ pub struct FileState {
     pub handle: Option<Handle>,
     pub canvas: Canvas,
     pub modified: bool,
+    pub history: History,
+    pub tileset: Tileset,
+    /// Metadata for art-scene formats, kept through loading and saving.
+    pub sauce: Option<Sauce>,
 }
 
 impl FileState {
-    pub fn new(path: Option<impl AsRef<Path>>) -> io::Result<FileState> {
+    pub fn new(path: Option<impl AsRef<Path>>) -> Result<FileState, LoadError> {
         if let Some(path) = path {
             load(path)
         } else {
@@ -21,6 +28,9 @@ impl FileState {
                 handle: None,
                 canvas: Canvas::default(),
                 modified: false,
+                history: History::default(),
+                tileset: Tileset::default(),
+                sauce: None,
             })
         }
     }
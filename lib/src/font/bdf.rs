@@ -0,0 +1,204 @@
+//! Glyph Bitmap Distribution Format, the X11 bitmap font format.
+//!
+//! Every glyph is placed in a cell the size of `FONTBOUNDINGBOX`, aligned on the font's baseline.
+//! Unicode fonts (`CHARSET_REGISTRY "ISO10646"`) keep their glyphs in file order and record each
+//! glyph's code point in the tileset's [`CharMap`]. For any other charset, a glyph's tile index
+//! is its `ENCODING`, so codepage fonts line up with the codepage.
+
+use super::{glyph_pixels, unpack_bitmap, Font, FontError};
+use crate::charmap::CharMap;
+
+/// Glyphs with a larger `ENCODING` are skipped in non-Unicode fonts.
+const MAX_ENCODING: u32 = 0xFFFF;
+/// Glyphs whose `ENCODING` would take the font past this many pixels are skipped too, as every
+/// code below theirs gets a cell.
+const MAX_FONT_PIXELS: usize = 1 << 26;
+
+#[derive(Default)]
+struct Glyph {
+    encoding: Option<u32>,
+    bbx: (u32, u32, i32, i32),
+    bitmap: Vec<u8>,
+}
+
+pub fn parse(text: &str) -> Result<Font, FontError> {
+    let mut bounding_box = None;
+    let mut registry = String::new();
+    let mut encoding = String::new();
+    let mut glyphs = Vec::new();
+    let mut glyph: Option<Glyph> = None;
+    let mut in_bitmap = false;
+
+    for (line_number, line) in text.lines().enumerate() {
+        let error = |message: &str| FontError::Bdf {
+            line: line_number + 1,
+            message: message.into(),
+        };
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let mut numbers = || -> Result<Vec<i32>, FontError> {
+            words
+                .by_ref()
+                .map(|word| word.parse().map_err(|_| error("expected a number")))
+                .collect()
+        };
+        match (keyword, glyph.as_mut()) {
+            ("FONTBOUNDINGBOX", _) => {
+                let [w, h, x, y] = numbers()?[..] else {
+                    return Err(error("FONTBOUNDINGBOX needs 4 numbers"));
+                };
+                let (w, h) = (w.max(0) as u32, h.max(0) as u32);
+                glyph_pixels(w, h)?;
+                bounding_box = Some((w, h, x, y));
+            }
+            ("CHARSET_REGISTRY", _) => registry = unquote(line, keyword),
+            ("CHARSET_ENCODING", _) => encoding = unquote(line, keyword),
+            ("STARTCHAR", _) => glyph = Some(Glyph::default()),
+            ("ENCODING", Some(glyph)) => {
+                let numbers = numbers()?;
+                // A negative encoding means the glyph has no code in the font's charset.
+                glyph.encoding = numbers.first().and_then(|&n| u32::try_from(n).ok());
+            }
+            ("BBX", Some(glyph)) => {
+                let [w, h, x, y] = numbers()?[..] else {
+                    return Err(error("BBX needs 4 numbers"));
+                };
+                glyph.bbx = (w.max(0) as u32, h.max(0) as u32, x, y);
+            }
+            ("BITMAP", Some(_)) => in_bitmap = true,
+            ("ENDCHAR", Some(_)) => {
+                in_bitmap = false;
+                glyphs.extend(glyph.take());
+            }
+            (hex, Some(glyph)) if in_bitmap => {
+                if hex.len() % 2 != 0 {
+                    return Err(error("odd number of hex digits in bitmap"));
+                }
+                for i in (0..hex.len()).step_by(2) {
+                    let byte = u8::from_str_radix(&hex[i..i + 2], 16)
+                        .map_err(|_| error("invalid hex in bitmap"))?;
+                    glyph.bitmap.push(byte);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let bounding_box = bounding_box.ok_or(FontError::Bdf {
+        line: 0,
+        message: "missing FONTBOUNDINGBOX".into(),
+    })?;
+    let unicode = registry.eq_ignore_ascii_case("ISO10646")
+        || (registry.eq_ignore_ascii_case("ISO8859") && encoding == "1");
+    let pixels = glyph_pixels(bounding_box.0, bounding_box.1)?;
+    let blank = vec![0; pixels];
+    let mut cells = Vec::new();
+    let mut charmap = CharMap::new();
+    for glyph in &glyphs {
+        let cell = render(glyph, bounding_box)?;
+        if unicode {
+            if let Some(c) = glyph.encoding.and_then(char::from_u32) {
+                charmap.insert(cells.len() as u32, c);
+            }
+            cells.push(cell);
+        } else if let Some(index) = glyph
+            .encoding
+            .filter(|&e| e <= MAX_ENCODING && (e as usize + 1) * pixels <= MAX_FONT_PIXELS)
+        {
+            let index = index as usize;
+            if index >= cells.len() {
+                cells.resize(index + 1, blank.clone());
+            }
+            cells[index] = cell;
+        }
+    }
+    Ok(Font {
+        glyph_size: (bounding_box.0, bounding_box.1),
+        glyphs: cells,
+        charmap: unicode.then_some(charmap),
+    })
+}
+
+/// Draws a glyph into a cell of the font bounding box, clipping anything outside it.
+fn render(
+    glyph: &Glyph,
+    (width, height, x_offset, y_offset): (u32, u32, i32, i32),
+) -> Result<Vec<u8>, FontError> {
+    let mut cell = vec![0; glyph_pixels(width, height)?];
+    let (glyph_width, glyph_height, glyph_x, glyph_y) = glyph.bbx;
+    if glyph_width == 0 || glyph_height == 0 {
+        return Ok(cell);
+    }
+    let pixels = unpack_bitmap(&glyph.bitmap, glyph_width, glyph_height)?;
+    // Offsets are measured from the baseline upwards, rows from the top downwards.
+    let left = glyph_x as i64 - x_offset as i64;
+    let top = (height as i64 + y_offset as i64) - (glyph_y as i64 + glyph_height as i64);
+    for (i, &coverage) in pixels.iter().enumerate() {
+        let x = left + (i as u32 % glyph_width) as i64;
+        let y = top + (i as u32 / glyph_width) as i64;
+        if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+            cell[(x as u32 + y as u32 * width) as usize] = coverage;
+        }
+    }
+    Ok(cell)
+}
+
+/// Returns the value of a property line, without the surrounding quotes.
+fn unquote(line: &str, keyword: &str) -> String {
+    line[keyword.len()..].trim().trim_matches('"').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font(registry: &str, glyphs: &str) -> String {
+        format!(
+            "STARTFONT 2.1\nFONTBOUNDINGBOX 4 3 0 -1\nSTARTPROPERTIES 1\n\
+             CHARSET_REGISTRY \"{registry}\"\nENDPROPERTIES\nCHARS 1\n{glyphs}ENDFONT\n"
+        )
+    }
+
+    /// A 2x2 glyph at the left of the baseline, with its top left pixel set.
+    fn glyph(encoding: i32) -> String {
+        format!("STARTCHAR g\nENCODING {encoding}\nBBX 2 2 0 0\nBITMAP\n80\n00\nENDCHAR\n")
+    }
+
+    #[test]
+    fn unicode_fonts_keep_file_order() {
+        let font = parse(&font("ISO10646", &(glyph(0x263A) + &glyph(0x41)))).unwrap();
+        assert_eq!(font.glyph_size, (4, 3));
+        assert_eq!(font.glyphs.len(), 2);
+        // One row above the baseline, one below, so the glyph's top row is the cell's first.
+        assert_eq!(font.glyphs[0], [255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let charmap = font.charmap.unwrap();
+        assert_eq!(charmap.char(0), Some('☺'));
+        assert_eq!(charmap.char(1), Some('A'));
+    }
+
+    #[test]
+    fn codepage_fonts_are_placed_by_encoding() {
+        let font = parse(&font("IBM", &(glyph(3) + &glyph(-1)))).unwrap();
+        assert!(font.charmap.is_none());
+        assert_eq!(font.glyphs.len(), 4);
+        assert!(font.glyphs[0].iter().all(|&coverage| coverage == 0));
+        assert_eq!(font.glyphs[3][0], 255);
+    }
+
+    #[test]
+    fn huge_glyph_sizes_are_rejected() {
+        let text = font("ISO10646", "").replace("4 3 0 -1", "100000 100000 0 0");
+        assert!(matches!(
+            parse(&text),
+            Err(FontError::InvalidGlyphSize { .. })
+        ));
+        let huge = glyph(65).replace("BBX 2 2 0 0", "BBX 70000 70000 -2147483648 0");
+        let text = font("ISO10646", &huge);
+        assert!(matches!(
+            parse(&text),
+            Err(FontError::InvalidGlyphSize { .. })
+        ));
+    }
+}
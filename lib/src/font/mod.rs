@@ -0,0 +1,122 @@
+//! Bitmap font import, turning console and X11 fonts into tilesets.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use image::{ImageBuffer, Luma};
+use thiserror::Error;
+
+use crate::charmap::CharMap;
+use crate::tileset::{Tileset, TilesetError};
+
+pub mod bdf;
+pub mod psf;
+
+/// Number of glyphs per row in the tileset image built from a font.
+pub const GLYPHS_PER_ROW: u32 = 16;
+
+/// Most pixels a glyph may have, enough for 256x256 glyphs. Sizes come straight from the font
+/// file, so this keeps a broken one from asking for an enormous allocation.
+const MAX_GLYPH_PIXELS: u32 = 1 << 16;
+
+#[derive(Debug, Error)]
+pub enum FontError {
+    #[error("couldn't read font: {0}")]
+    Io(#[from] io::Error),
+    #[error("unrecognised font format")]
+    UnknownFormat,
+    #[error("font file is truncated")]
+    Truncated,
+    #[error("font has no glyphs")]
+    Empty,
+    #[error("invalid glyph size {width}x{height}")]
+    InvalidGlyphSize { width: u32, height: u32 },
+    #[error("line {line}: {message}")]
+    Bdf { line: usize, message: String },
+    #[error(transparent)]
+    Tileset(#[from] TilesetError),
+}
+
+/// Glyphs decoded from a font file, before they are laid out as a tileset.
+#[derive(Clone, Debug)]
+pub struct Font {
+    pub glyph_size: (u32, u32),
+    /// Coverage of each glyph, `glyph_size.0 * glyph_size.1` bytes in row-major order.
+    pub glyphs: Vec<Vec<u8>>,
+    pub charmap: Option<CharMap>,
+}
+
+impl Font {
+    /// Lays the glyphs out in rows of [`GLYPHS_PER_ROW`], in glyph order.
+    pub fn into_tileset(self) -> Result<Tileset, FontError> {
+        let (width, height) = self.glyph_size;
+        if self.glyphs.is_empty() {
+            return Err(FontError::Empty);
+        }
+        let rows = (self.glyphs.len() as u32).div_ceil(GLYPHS_PER_ROW);
+        let mut image = ImageBuffer::new(width * GLYPHS_PER_ROW, height * rows);
+        for (index, glyph) in self.glyphs.iter().enumerate() {
+            let index = index as u32;
+            let x_offset = index % GLYPHS_PER_ROW * width;
+            let y_offset = index / GLYPHS_PER_ROW * height;
+            for (i, &coverage) in glyph.iter().enumerate() {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                image.put_pixel(x_offset + x, y_offset + y, Luma([coverage]));
+            }
+        }
+        let tileset = Tileset::new(image, self.glyph_size)?;
+        Ok(match self.charmap {
+            Some(charmap) if !charmap.is_empty() => tileset.with_charmap(charmap),
+            _ => tileset,
+        })
+    }
+}
+
+/// Loads a PSF1, PSF2 or BDF font, optionally gzip-compressed as console fonts often are.
+pub fn load(path: impl AsRef<Path>) -> Result<Tileset, FontError> {
+    let data = fs::read(path)?;
+    parse(&data)?.into_tileset()
+}
+
+/// Detects the font format from its contents and decodes it.
+pub fn parse(data: &[u8]) -> Result<Font, FontError> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut decompressed)?;
+        return parse(&decompressed);
+    }
+    if data.starts_with(&psf::PSF1_MAGIC) || data.starts_with(&psf::PSF2_MAGIC) {
+        psf::parse(data)
+    } else if data.starts_with(b"STARTFONT") {
+        let text = String::from_utf8_lossy(data);
+        bdf::parse(&text)
+    } else {
+        Err(FontError::UnknownFormat)
+    }
+}
+
+/// The number of pixels in a glyph of the given size, if it is a size we accept.
+fn glyph_pixels(width: u32, height: u32) -> Result<usize, FontError> {
+    width
+        .checked_mul(height)
+        .filter(|&pixels| pixels > 0 && pixels <= MAX_GLYPH_PIXELS)
+        .map(|pixels| pixels as usize)
+        .ok_or(FontError::InvalidGlyphSize { width, height })
+}
+
+/// Expands a 1-bit-per-pixel, MSB-first glyph bitmap into coverage bytes.
+fn unpack_bitmap(bitmap: &[u8], width: u32, height: u32) -> Result<Vec<u8>, FontError> {
+    let row_bytes = width.div_ceil(8) as usize;
+    let mut glyph = Vec::with_capacity(glyph_pixels(width, height)?);
+    for row in bitmap.chunks(row_bytes).take(height as usize) {
+        for x in 0..width as usize {
+            let set = row
+                .get(x / 8)
+                .is_some_and(|byte| byte & (0x80 >> (x % 8)) != 0);
+            glyph.push(if set { u8::MAX } else { 0 });
+        }
+    }
+    Ok(glyph)
+}
@@ -0,0 +1,242 @@
+//! PC Screen Font, the Linux console font format, in both its versions.
+
+use super::{unpack_bitmap, Font, FontError};
+use crate::charmap::CharMap;
+
+pub const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+pub const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+
+const PSF1_MODE_512: u8 = 0x01;
+const PSF1_MODE_HAS_TAB: u8 = 0x02;
+const PSF1_MODE_HAS_SEQ: u8 = 0x04;
+const PSF1_SEPARATOR: u16 = 0xFFFF;
+const PSF1_START_SEQ: u16 = 0xFFFE;
+
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xFF;
+const PSF2_START_SEQ: u8 = 0xFE;
+
+pub fn parse(data: &[u8]) -> Result<Font, FontError> {
+    if data.starts_with(&PSF2_MAGIC) {
+        parse_psf2(data)
+    } else if data.starts_with(&PSF1_MAGIC) {
+        parse_psf1(data)
+    } else {
+        Err(FontError::UnknownFormat)
+    }
+}
+
+fn parse_psf1(data: &[u8]) -> Result<Font, FontError> {
+    let header = data.get(..4).ok_or(FontError::Truncated)?;
+    let (mode, height) = (header[2], header[3] as u32);
+    let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+    let glyph_bytes = height as usize;
+    let glyphs_end = 4 + count * glyph_bytes;
+    let bitmaps = data.get(4..glyphs_end).ok_or(FontError::Truncated)?;
+    let glyphs = glyphs(bitmaps, glyph_bytes, 8, height)?;
+
+    let charmap = if mode & (PSF1_MODE_HAS_TAB | PSF1_MODE_HAS_SEQ) != 0 {
+        let mut charmap = CharMap::new();
+        let mut index = 0;
+        let mut in_sequence = false;
+        for entry in data[glyphs_end..].chunks_exact(2) {
+            match u16::from_le_bytes([entry[0], entry[1]]) {
+                PSF1_SEPARATOR => {
+                    index += 1;
+                    in_sequence = false;
+                }
+                PSF1_START_SEQ => in_sequence = true,
+                // Sequences combine several code points into one glyph, which a single
+                // character can't represent.
+                _ if in_sequence => {}
+                code => {
+                    if let Some(c) = char::from_u32(code as u32) {
+                        charmap.insert(index, c);
+                    }
+                }
+            }
+        }
+        Some(charmap)
+    } else {
+        None
+    };
+    Ok(Font {
+        glyph_size: (8, height),
+        glyphs,
+        charmap,
+    })
+}
+
+fn parse_psf2(data: &[u8]) -> Result<Font, FontError> {
+    let header = data.get(..32).ok_or(FontError::Truncated)?;
+    let field = |n: usize| u32::from_le_bytes(header[n * 4..n * 4 + 4].try_into().unwrap());
+    let header_size = field(2) as usize;
+    let flags = field(3);
+    let count = field(4) as usize;
+    let glyph_bytes = field(5) as usize;
+    let (height, width) = (field(6), field(7));
+    if width.div_ceil(8) as usize * height as usize > glyph_bytes {
+        return Err(FontError::InvalidGlyphSize { width, height });
+    }
+    let glyphs_end = count
+        .checked_mul(glyph_bytes)
+        .and_then(|len| len.checked_add(header_size))
+        .ok_or(FontError::Truncated)?;
+    let bitmaps = data
+        .get(header_size..glyphs_end)
+        .ok_or(FontError::Truncated)?;
+    let glyphs = glyphs(bitmaps, glyph_bytes, width, height)?;
+
+    let charmap = if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+        let mut charmap = CharMap::new();
+        let table = &data[glyphs_end..];
+        for (index, entry) in table
+            .split(|&b| b == PSF2_SEPARATOR)
+            .take(count)
+            .enumerate()
+        {
+            // Anything after the first sequence marker is a sequence, which we skip.
+            let singles = entry
+                .split(|&b| b == PSF2_START_SEQ)
+                .next()
+                .unwrap_or_default();
+            for c in String::from_utf8_lossy(singles).chars() {
+                if c != char::REPLACEMENT_CHARACTER {
+                    charmap.insert(index as u32, c);
+                }
+            }
+        }
+        Some(charmap)
+    } else {
+        None
+    };
+    Ok(Font {
+        glyph_size: (width, height),
+        glyphs,
+        charmap,
+    })
+}
+
+fn glyphs(
+    bitmaps: &[u8],
+    glyph_bytes: usize,
+    width: u32,
+    height: u32,
+) -> Result<Vec<Vec<u8>>, FontError> {
+    if width == 0 || height == 0 || glyph_bytes == 0 {
+        return Err(FontError::InvalidGlyphSize { width, height });
+    }
+    bitmaps
+        .chunks_exact(glyph_bytes)
+        .map(|bitmap| unpack_bitmap(bitmap, width, height))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PSF1 font of 256 two-row glyphs, glyph `n` having `n` as its first row.
+    fn psf1(table: &[u16]) -> Vec<u8> {
+        let mode = if table.is_empty() {
+            0
+        } else {
+            PSF1_MODE_HAS_TAB
+        };
+        let mut data = vec![PSF1_MAGIC[0], PSF1_MAGIC[1], mode, 2];
+        for n in 0..=255u8 {
+            data.extend_from_slice(&[n, 0]);
+        }
+        for entry in table {
+            data.extend_from_slice(&entry.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn reads_psf1_glyphs_and_table() {
+        let font = parse(&psf1(&[])).unwrap();
+        assert_eq!(font.glyph_size, (8, 2));
+        assert_eq!(font.glyphs.len(), 256);
+        assert_eq!(font.glyphs[0x81][..8], [255, 0, 0, 0, 0, 0, 0, 255]);
+        assert!(font.glyphs[0x81][8..].iter().all(|&coverage| coverage == 0));
+        assert!(font.charmap.is_none());
+
+        let table = [0x41, PSF1_SEPARATOR, 0x42, 0x391, PSF1_SEPARATOR];
+        let charmap = parse(&psf1(&table)).unwrap().charmap.unwrap();
+        assert_eq!(charmap.char(0), Some('A'));
+        assert_eq!(charmap.char(1), Some('B'));
+        assert_eq!(charmap.index('Α'), Some(1));
+    }
+
+    #[test]
+    fn truncated_psf1_is_rejected() {
+        let data = psf1(&[]);
+        assert!(matches!(parse(&data[..100]), Err(FontError::Truncated)));
+    }
+
+    fn psf2(width: u32, height: u32, glyphs: &[&[u8]], table: &[u8]) -> Vec<u8> {
+        let glyph_bytes = width.div_ceil(8) * height;
+        let flags = if table.is_empty() {
+            0
+        } else {
+            PSF2_HAS_UNICODE_TABLE
+        };
+        let mut data = PSF2_MAGIC.to_vec();
+        let fields = [
+            0,
+            32,
+            flags,
+            glyphs.len() as u32,
+            glyph_bytes,
+            height,
+            width,
+        ];
+        for field in fields {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        for glyph in glyphs {
+            data.extend_from_slice(glyph);
+        }
+        data.extend_from_slice(table);
+        data
+    }
+
+    #[test]
+    fn reads_psf2_glyphs_and_table() {
+        // 10 pixels wide, so each row takes two bytes.
+        let glyphs: [&[u8]; 2] = [&[0x80, 0x40], &[0x00, 0x00]];
+        let table = "é\u{ff}x\u{fe}ab\u{ff}";
+        let table: Vec<u8> = table
+            .chars()
+            .flat_map(|c| match c {
+                '\u{ff}' => vec![PSF2_SEPARATOR],
+                '\u{fe}' => vec![PSF2_START_SEQ],
+                c => c.to_string().into_bytes(),
+            })
+            .collect();
+        let font = parse(&psf2(10, 1, &glyphs, &table)).unwrap();
+        assert_eq!(font.glyph_size, (10, 1));
+        let mut first = vec![0; 10];
+        first[0] = 255;
+        first[9] = 255;
+        assert_eq!(font.glyphs[0], first);
+        let charmap = font.charmap.unwrap();
+        assert_eq!(charmap.char(0), Some('é'));
+        // Sequences are skipped.
+        assert_eq!(charmap.char(1), Some('x'));
+        assert_eq!(charmap.index('a'), None);
+    }
+
+    #[test]
+    fn psf2_glyph_size_must_fit() {
+        let data = psf2(8, 2, &[&[0, 0]], &[]);
+        let mut huge = data.clone();
+        huge[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            parse(&huge),
+            Err(FontError::InvalidGlyphSize { .. })
+        ));
+        assert!(parse(&data).is_ok());
+    }
+}
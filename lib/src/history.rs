@@ -0,0 +1,286 @@
+use std::collections::VecDeque;
+
+use crate::canvas::{Action, Canvas, TileChange};
+
+/// Default limit on the number of tile changes kept across all undo steps.
+pub const DEFAULT_MAX_CHANGES: usize = 1 << 20;
+
+/// One undo step.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Tiles(Vec<TileChange>),
+    /// The whole canvas before and after an action that changed its size.
+    Resize {
+        before: Canvas,
+        after: Canvas,
+    },
+}
+
+impl Edit {
+    /// Number of tile changes stored by this edit, used for the memory limit.
+    pub fn size(&self) -> usize {
+        match self {
+            Edit::Tiles(changes) => changes.len(),
+            Edit::Resize { before, after } => before.tiles.len() + after.tiles.len(),
+        }
+    }
+
+    fn revert(&self, canvas: &mut Canvas) {
+        match self {
+            Edit::Tiles(changes) => {
+                for change in changes.iter().rev() {
+                    canvas.set_tile(change.x, change.y, change.before);
+                }
+            }
+            Edit::Resize { before, .. } => *canvas = before.clone(),
+        }
+    }
+
+    fn reapply(&self, canvas: &mut Canvas) {
+        match self {
+            Edit::Tiles(changes) => {
+                for change in changes {
+                    canvas.set_tile(change.x, change.y, change.after);
+                }
+            }
+            Edit::Resize { after, .. } => *canvas = after.clone(),
+        }
+    }
+}
+
+/// Undo/redo history for a canvas.
+///
+/// Actions applied between [`History::begin_group`] and [`History::end_group`]
+/// are merged into a single undo step, which is how a mouse drag is recorded.
+#[derive(Debug)]
+pub struct History {
+    undo_stack: VecDeque<Edit>,
+    redo_stack: Vec<Edit>,
+    group: Option<Vec<TileChange>>,
+    max_changes: usize,
+    stored_changes: usize,
+}
+
+impl History {
+    pub fn new(max_changes: usize) -> History {
+        History {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            group: None,
+            max_changes,
+            stored_changes: 0,
+        }
+    }
+
+    /// Applies an action to the canvas and records it.
+    ///
+    /// Returns `false` if the action didn't change anything.
+    pub fn apply(&mut self, canvas: &mut Canvas, action: Action) -> bool {
+        if action.resizes() {
+            let before = canvas.clone();
+            canvas.handle_action(action);
+            if *canvas == before {
+                return false;
+            }
+            self.end_group();
+            self.push(Edit::Resize {
+                before,
+                after: canvas.clone(),
+            });
+            return true;
+        }
+        let changes = canvas.handle_action(action);
+        if changes.is_empty() {
+            return false;
+        }
+        self.redo_stack.clear();
+        match &mut self.group {
+            Some(group) => group.extend(changes),
+            None => self.push(Edit::Tiles(changes)),
+        }
+        true
+    }
+
+    /// Starts merging subsequent actions into one undo step.
+    pub fn begin_group(&mut self) {
+        self.end_group();
+        self.group = Some(Vec::new());
+    }
+
+    /// Finishes the current group, if any.
+    pub fn end_group(&mut self) {
+        if let Some(changes) = self.group.take() {
+            if !changes.is_empty() {
+                self.push(Edit::Tiles(changes));
+            }
+        }
+    }
+
+    /// Reverts the most recent edit. Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self, canvas: &mut Canvas) -> bool {
+        self.end_group();
+        let Some(edit) = self.undo_stack.pop_back() else {
+            return false;
+        };
+        self.stored_changes -= edit.size();
+        edit.revert(canvas);
+        self.redo_stack.push(edit);
+        true
+    }
+
+    /// Reapplies the most recently undone edit. Returns `false` if there was nothing to redo.
+    pub fn redo(&mut self, canvas: &mut Canvas) -> bool {
+        self.end_group();
+        let Some(edit) = self.redo_stack.pop() else {
+            return false;
+        };
+        edit.reapply(canvas);
+        self.push_undo(edit);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty() || self.group.as_ref().is_some_and(|g| !g.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.group = None;
+        self.stored_changes = 0;
+    }
+
+    fn push(&mut self, edit: Edit) {
+        self.redo_stack.clear();
+        self.push_undo(edit);
+    }
+
+    fn push_undo(&mut self, edit: Edit) {
+        self.stored_changes += edit.size();
+        self.undo_stack.push_back(edit);
+        // Drop the oldest steps once over the limit, but always keep the newest one.
+        while self.stored_changes > self.max_changes && self.undo_stack.len() > 1 {
+            if let Some(oldest) = self.undo_stack.pop_front() {
+                self.stored_changes -= oldest.size();
+            }
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new(DEFAULT_MAX_CHANGES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Tile;
+
+    fn set(x: u32, index: u32) -> Action {
+        Action::SetTile {
+            x,
+            y: 0,
+            tile: Tile {
+                index,
+                ..Tile::default()
+            },
+        }
+    }
+
+    fn indices(canvas: &Canvas) -> Vec<u32> {
+        canvas.tiles.iter().map(|tile| tile.index).collect()
+    }
+
+    #[test]
+    fn undo_and_redo_step_through_edits() {
+        let mut canvas = Canvas::new(3, 1);
+        let mut history = History::default();
+        assert!(!history.can_undo());
+        assert!(history.apply(&mut canvas, set(0, 1)));
+        assert!(history.apply(&mut canvas, set(1, 2)));
+        // Setting a tile to what it already is isn't recorded.
+        assert!(!history.apply(&mut canvas, set(1, 2)));
+        assert_eq!(indices(&canvas), [1, 2, 32]);
+
+        assert!(history.undo(&mut canvas));
+        assert_eq!(indices(&canvas), [1, 32, 32]);
+        assert!(history.undo(&mut canvas));
+        assert_eq!(indices(&canvas), [32, 32, 32]);
+        assert!(!history.undo(&mut canvas));
+
+        assert!(history.redo(&mut canvas));
+        assert!(history.redo(&mut canvas));
+        assert_eq!(indices(&canvas), [1, 2, 32]);
+        assert!(!history.redo(&mut canvas));
+    }
+
+    #[test]
+    fn group_is_one_step() {
+        let mut canvas = Canvas::new(3, 1);
+        let mut history = History::default();
+        history.begin_group();
+        history.apply(&mut canvas, set(0, 1));
+        history.apply(&mut canvas, set(1, 2));
+        history.apply(&mut canvas, set(0, 3));
+        assert!(history.can_undo());
+        history.end_group();
+        assert_eq!(indices(&canvas), [3, 2, 32]);
+
+        assert!(history.undo(&mut canvas));
+        assert_eq!(indices(&canvas), [32, 32, 32]);
+        assert!(!history.can_undo());
+        assert!(history.redo(&mut canvas));
+        assert_eq!(indices(&canvas), [3, 2, 32]);
+    }
+
+    #[test]
+    fn empty_group_leaves_no_step() {
+        let mut canvas = Canvas::new(1, 1);
+        let mut history = History::default();
+        history.begin_group();
+        history.end_group();
+        assert!(!history.can_undo());
+        assert!(!history.undo(&mut canvas));
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut canvas = Canvas::new(2, 1);
+        let mut history = History::default();
+        history.apply(&mut canvas, set(0, 1));
+        history.undo(&mut canvas);
+        assert!(history.can_redo());
+        history.apply(&mut canvas, set(1, 2));
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut canvas));
+        assert_eq!(indices(&canvas), [32, 2]);
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped_past_the_limit() {
+        let mut canvas = Canvas::new(4, 1);
+        let mut history = History::new(2);
+        for x in 0..4 {
+            history.apply(&mut canvas, set(x, 1));
+        }
+        assert!(history.undo(&mut canvas));
+        assert!(history.undo(&mut canvas));
+        assert!(!history.undo(&mut canvas));
+        assert_eq!(indices(&canvas), [1, 1, 32, 32]);
+
+        // A single step over the limit is still kept.
+        let mut history = History::new(1);
+        history.begin_group();
+        history.apply(&mut canvas, set(2, 5));
+        history.apply(&mut canvas, set(3, 5));
+        history.end_group();
+        assert!(history.undo(&mut canvas));
+        assert_eq!(indices(&canvas), [1, 1, 32, 32]);
+    }
+}
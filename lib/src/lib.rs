@@ -1,4 +1,11 @@
 pub mod canvas;
+pub mod charmap;
+pub mod convert;
 pub mod file_formats;
 pub mod file_state;
+pub mod font;
+pub mod history;
+pub mod render;
+pub mod sauce;
+pub mod shape;
 pub mod tileset;
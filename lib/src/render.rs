@@ -0,0 +1,144 @@
+//! Rendering canvases to images, without any GUI.
+
+use image::{imageops, GenericImageView, Pixel, RgbaImage};
+use thiserror::Error;
+
+use crate::canvas::{Canvas, Tile};
+use crate::tileset::Tileset;
+
+/// Most pixels a rendered image may have, a gigabyte of RGBA.
+const MAX_PIXELS: u64 = 1 << 28;
+
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("image would be too large to render")]
+    TooLarge,
+}
+
+/// Renders a canvas, scaling every pixel up to a `scale`x`scale` square.
+///
+/// Tiles whose index is outside the tileset are drawn as their background colour.
+pub fn render(canvas: &Canvas, tileset: &Tileset, scale: u32) -> Result<RgbaImage, RenderError> {
+    let (tile_width, tile_height) = tileset.tile_size;
+    let (width, height) = image_size(canvas.size(), (tile_width, tile_height))?;
+    // Check the final size before drawing anything.
+    image_size((width, height), (scale.max(1), scale.max(1)))?;
+    let mut image = RgbaImage::new(width, height);
+    for (x, y, tile) in canvas.tiles() {
+        draw_tile(&mut image, x * tile_width, y * tile_height, *tile, tileset);
+    }
+    Ok(upscale(image, scale))
+}
+
+/// The size of an image of `count` cells of `cell` pixels each, if it isn't too large.
+pub fn image_size(count: (u32, u32), cell: (u32, u32)) -> Result<(u32, u32), RenderError> {
+    let width = count.0.checked_mul(cell.0).ok_or(RenderError::TooLarge)?;
+    let height = count.1.checked_mul(cell.1).ok_or(RenderError::TooLarge)?;
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(RenderError::TooLarge);
+    }
+    Ok((width, height))
+}
+
+/// Renders a single tile at its natural size.
+pub fn render_tile(tile: Tile, tileset: &Tileset) -> RgbaImage {
+    let (tile_width, tile_height) = tileset.tile_size;
+    let mut image = RgbaImage::new(tile_width, tile_height);
+    draw_tile(&mut image, 0, 0, tile, tileset);
+    image
+}
+
+fn draw_tile(image: &mut RgbaImage, x_offset: u32, y_offset: u32, tile: Tile, tileset: &Tileset) {
+    let (tile_width, tile_height) = tileset.tile_size;
+    let background = tile.bg.into();
+    let Some(glyph) = tileset.tile(tile.index) else {
+        for y in 0..tile_height {
+            for x in 0..tile_width {
+                image.put_pixel(x_offset + x, y_offset + y, background);
+            }
+        }
+        return;
+    };
+    let mut foreground: image::Rgba<u8> = tile.fg.into();
+    for (x, y, blend) in glyph.pixels() {
+        foreground.0[3] = blend.0[0];
+        let mut color = background;
+        color.blend(&foreground);
+        image.put_pixel(x_offset + x, y_offset + y, color);
+    }
+}
+
+/// Scales the image up, which [`render`] has already checked is possible.
+fn upscale(image: RgbaImage, scale: u32) -> RgbaImage {
+    if scale <= 1 {
+        return image;
+    }
+    let (width, height) = image.dimensions();
+    imageops::resize(
+        &image,
+        width * scale,
+        height * scale,
+        imageops::FilterType::Nearest,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Rgba};
+
+    use super::*;
+    use crate::canvas::Color;
+
+    const RED: Color = Color::rgb(255, 0, 0);
+    const BLUE: Color = Color::rgb(0, 0, 255);
+
+    fn tile(index: u32) -> Tile {
+        Tile {
+            index,
+            fg: RED,
+            bg: BLUE,
+        }
+    }
+
+    /// Two 2x1 tiles: an empty one and a half covered one.
+    fn tileset() -> Tileset {
+        let image = ImageBuffer::from_raw(4, 1, vec![0, 0, u8::MAX, 0]).unwrap();
+        Tileset::new(image, (2, 1)).unwrap()
+    }
+
+    #[test]
+    fn renders_glyphs_in_tile_colours() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_all_tiles(vec![tile(0), tile(1)]);
+        let image = render(&canvas, &tileset(), 2).unwrap();
+        let (red, blue): (Rgba<u8>, Rgba<u8>) = (RED.into(), BLUE.into());
+        assert_eq!(image.dimensions(), (8, 2));
+        for y in 0..2 {
+            let row: Vec<Rgba<u8>> = (0..8).map(|x| *image.get_pixel(x, y)).collect();
+            assert_eq!(row, [blue, blue, blue, blue, red, red, blue, blue]);
+        }
+    }
+
+    #[test]
+    fn tiles_outside_the_tileset_show_their_background() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set_tile(0, 0, tile(2));
+        let image = render(&canvas, &tileset(), 1).unwrap();
+        let blue: Rgba<u8> = BLUE.into();
+        assert!(image.pixels().all(|&pixel| pixel == blue));
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let tileset = Tileset::default();
+        let canvas = Canvas::new(2, 2);
+        assert!(matches!(
+            render(&canvas, &tileset, u32::MAX),
+            Err(RenderError::TooLarge)
+        ));
+        assert!(matches!(
+            render(&canvas, &tileset, 1 << 14),
+            Err(RenderError::TooLarge)
+        ));
+    }
+}
@@ -0,0 +1,234 @@
+//! SAUCE, the Standard Architecture for Universal Comment Extensions: a 128-byte record at the
+//! end of art-scene files with the title, author and details needed to display them.
+//!
+//! A file with a record ends with an end of file marker, an optional block of comment lines and
+//! the record itself. Text fields are CP437, padded with spaces.
+
+use serde::{Deserialize, Serialize};
+
+use crate::charmap::CharMap;
+
+const RECORD_SIZE: usize = 128;
+const RECORD_ID: &[u8; 5] = b"SAUCE";
+const COMMENT_ID: &[u8; 5] = b"COMNT";
+const COMMENT_LINE_SIZE: usize = 64;
+const VERSION: &[u8; 2] = b"00";
+const EOF: u8 = 0x1A;
+
+pub const TITLE_LEN: usize = 35;
+pub const AUTHOR_LEN: usize = 20;
+pub const GROUP_LEN: usize = 20;
+pub const DATE_LEN: usize = 8;
+pub const FONT_LEN: usize = 22;
+
+/// Data type for text-mode files, which [`FILE_TYPE_ANSI`] and friends belong to.
+pub const DATA_TYPE_CHARACTER: u8 = 1;
+pub const DATA_TYPE_XBIN: u8 = 6;
+pub const FILE_TYPE_ANSI: u8 = 1;
+
+/// Flag for the background colours being bright instead of blinking, known as iCE colours.
+pub const FLAG_NON_BLINK: u8 = 0x01;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sauce {
+    pub title: String,
+    pub author: String,
+    pub group: String,
+    /// Creation date as `CCYYMMDD`.
+    pub date: String,
+    pub data_type: u8,
+    pub file_type: u8,
+    /// Type dependent numbers. For text-mode files the first two are the width and height in
+    /// characters.
+    pub info: [u16; 4],
+    pub flags: u8,
+    /// Name of the font the file is meant to be shown with, like `IBM VGA`.
+    pub font: String,
+    pub comments: Vec<String>,
+}
+
+impl Sauce {
+    /// Splits a file into its contents and SAUCE record, if it has one. The end of file marker
+    /// and comment block are not part of the contents.
+    pub fn split(data: &[u8]) -> (&[u8], Option<Sauce>) {
+        let Some(start) = data.len().checked_sub(RECORD_SIZE) else {
+            return (data, None);
+        };
+        let record = &data[start..];
+        if !record.starts_with(RECORD_ID) {
+            return (data, None);
+        }
+        let charmap = CharMap::cp437();
+        let text = |range: std::ops::Range<usize>| decode(&charmap, &record[range]);
+        let u16_at = |n: usize| u16::from_le_bytes([record[n], record[n + 1]]);
+
+        let comment_lines = record[104] as usize;
+        let mut contents_end = start;
+        let mut comments = Vec::new();
+        let comments_size = COMMENT_ID.len() + comment_lines * COMMENT_LINE_SIZE;
+        if let Some(comments_start) = start.checked_sub(comments_size) {
+            let block = &data[comments_start..start];
+            if comment_lines > 0 && block.starts_with(COMMENT_ID) {
+                comments = block[COMMENT_ID.len()..]
+                    .chunks(COMMENT_LINE_SIZE)
+                    .map(|line| decode(&charmap, line))
+                    .collect();
+                contents_end = comments_start;
+            }
+        }
+        if contents_end > 0 && data[contents_end - 1] == EOF {
+            contents_end -= 1;
+        }
+
+        let sauce = Sauce {
+            title: text(7..42),
+            author: text(42..62),
+            group: text(62..82),
+            date: text(82..90),
+            data_type: record[94],
+            file_type: record[95],
+            info: [u16_at(96), u16_at(98), u16_at(100), u16_at(102)],
+            flags: record[105],
+            font: text(106..128),
+            comments,
+        };
+        (&data[..contents_end], Some(sauce))
+    }
+
+    /// Appends the end of file marker, comments and record to a file's contents.
+    pub fn append_to(&self, data: &mut Vec<u8>) {
+        let file_size = data.len().try_into().unwrap_or(u32::MAX);
+        let charmap = CharMap::cp437();
+        data.push(EOF);
+        let comments = &self.comments[..self.comments.len().min(u8::MAX as usize)];
+        if !comments.is_empty() {
+            data.extend_from_slice(COMMENT_ID);
+            for line in comments {
+                data.extend(encode(&charmap, line, COMMENT_LINE_SIZE, b' '));
+            }
+        }
+        data.extend_from_slice(RECORD_ID);
+        data.extend_from_slice(VERSION);
+        data.extend(encode(&charmap, &self.title, TITLE_LEN, b' '));
+        data.extend(encode(&charmap, &self.author, AUTHOR_LEN, b' '));
+        data.extend(encode(&charmap, &self.group, GROUP_LEN, b' '));
+        data.extend(encode(&charmap, &self.date, DATE_LEN, b' '));
+        data.extend_from_slice(&file_size.to_le_bytes());
+        data.push(self.data_type);
+        data.push(self.file_type);
+        for info in self.info {
+            data.extend_from_slice(&info.to_le_bytes());
+        }
+        data.push(comments.len() as u8);
+        data.push(self.flags);
+        // The font name is zero-padded rather than space-padded.
+        data.extend(encode(&charmap, self.font.trim_end(), FONT_LEN, 0));
+    }
+
+    /// Width in characters, for text-mode files that record one.
+    pub fn width(&self) -> Option<u32> {
+        match (self.data_type, self.info[0]) {
+            (DATA_TYPE_CHARACTER | DATA_TYPE_XBIN, width) if width > 0 => Some(width as u32),
+            _ => None,
+        }
+    }
+
+    /// Height in lines, for text-mode files that record one.
+    pub fn height(&self) -> Option<u32> {
+        match (self.data_type, self.info[1]) {
+            (DATA_TYPE_CHARACTER | DATA_TYPE_XBIN, height) if height > 0 => Some(height as u32),
+            _ => None,
+        }
+    }
+
+    pub fn ice_colors(&self) -> bool {
+        self.flags & FLAG_NON_BLINK != 0
+    }
+}
+
+fn decode(charmap: &CharMap, bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    bytes[..end]
+        .iter()
+        .map(|&b| charmap.char(b as u32).unwrap_or(' '))
+        .collect::<String>()
+        .trim_end()
+        .to_owned()
+}
+
+/// Encodes a field as CP437, cut or padded to `len` bytes.
+fn encode<'a>(
+    charmap: &'a CharMap,
+    text: &'a str,
+    len: usize,
+    padding: u8,
+) -> impl Iterator<Item = u8> + 'a {
+    text.chars()
+        .map(|c| match charmap.index(c) {
+            Some(index) if index <= u8::MAX as u32 => index as u8,
+            _ => b'?',
+        })
+        .chain(std::iter::repeat(padding))
+        .take(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sauce() -> Sauce {
+        Sauce {
+            title: "Café".into(),
+            author: "someone".into(),
+            group: "group".into(),
+            date: "20240131".into(),
+            data_type: DATA_TYPE_CHARACTER,
+            file_type: FILE_TYPE_ANSI,
+            info: [80, 25, 0, 0],
+            flags: FLAG_NON_BLINK,
+            font: "IBM VGA".into(),
+            comments: vec!["first line".into(), "second line".into()],
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_record_and_comments() {
+        let mut data = b"contents".to_vec();
+        sauce().append_to(&mut data);
+        assert_eq!(
+            data.len(),
+            8 + 1 + COMMENT_ID.len() + 2 * COMMENT_LINE_SIZE + RECORD_SIZE
+        );
+        assert_eq!(data[8], EOF);
+        assert_eq!(&data[9..14], COMMENT_ID);
+        let (contents, record) = Sauce::split(&data);
+        assert_eq!(contents, b"contents");
+        let record = record.unwrap();
+        assert_eq!(record, sauce());
+        assert_eq!((record.width(), record.height()), (Some(80), Some(25)));
+        assert!(record.ice_colors());
+    }
+
+    #[test]
+    fn round_trip_without_comments() {
+        let sauce = Sauce {
+            comments: Vec::new(),
+            ..sauce()
+        };
+        let mut data = Vec::new();
+        sauce.append_to(&mut data);
+        assert_eq!(data.len(), 1 + RECORD_SIZE);
+        let (contents, record) = Sauce::split(&data);
+        assert!(contents.is_empty());
+        assert_eq!(record, Some(sauce));
+    }
+
+    #[test]
+    fn files_without_a_record_are_left_alone() {
+        let short = b"hello\x1a";
+        assert_eq!(Sauce::split(short), (&short[..], None));
+        let long = vec![b'x'; 300];
+        assert_eq!(Sauce::split(&long), (&long[..], None));
+    }
+}
@@ -0,0 +1,207 @@
+//! Tiles covered by lines, rectangles and ellipses drawn between two corners.
+
+use std::collections::BTreeSet;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shape {
+    Line,
+    Rectangle {
+        filled: bool,
+    },
+    /// Ellipse fitting inside the rectangle between the two corners.
+    Ellipse {
+        filled: bool,
+    },
+}
+
+impl Shape {
+    /// Every tile the shape covers, each once.
+    pub fn points(self, start: (u32, u32), end: (u32, u32)) -> Vec<(u32, u32)> {
+        match self {
+            Shape::Line => line(start, end),
+            Shape::Rectangle { filled } => rectangle(start, end, filled),
+            Shape::Ellipse { filled } => ellipse(start, end, filled),
+        }
+    }
+}
+
+/// Bresenham's line, from `start` to `end` inclusive.
+pub fn line(start: (u32, u32), end: (u32, u32)) -> Vec<(u32, u32)> {
+    let (mut x, mut y) = (start.0 as i64, start.1 as i64);
+    let (end_x, end_y) = (end.0 as i64, end.1 as i64);
+    let dx = (end_x - x).abs();
+    let dy = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut points = Vec::new();
+    loop {
+        points.push((x as u32, y as u32));
+        if x == end_x && y == end_y {
+            return points;
+        }
+        if 2 * error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if 2 * error <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+fn bounds(a: (u32, u32), b: (u32, u32)) -> (u32, u32, u32, u32) {
+    (a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1))
+}
+
+pub fn rectangle(a: (u32, u32), b: (u32, u32), filled: bool) -> Vec<(u32, u32)> {
+    let (left, top, right, bottom) = bounds(a, b);
+    let mut points = Vec::new();
+    for y in top..=bottom {
+        for x in left..=right {
+            if filled || x == left || x == right || y == top || y == bottom {
+                points.push((x, y));
+            }
+        }
+    }
+    points
+}
+
+/// The span `centre ± half` rounded to whole tiles, rounding symmetrically around the centre.
+fn span(centre: f64, half: f64) -> (u32, u32) {
+    let high = (centre + half + 0.5).floor();
+    let low = 2.0 * centre - high;
+    (low as u32, high as u32)
+}
+
+/// Half the width of an ellipse with radii `radius` and `other` at `offset` from the centre along
+/// the other axis.
+fn half_width(radius: f64, other: f64, offset: f64) -> f64 {
+    if other == 0.0 {
+        return radius;
+    }
+    let t = (offset / other).clamp(-1.0, 1.0);
+    radius * (1.0 - t * t).sqrt()
+}
+
+pub fn ellipse(a: (u32, u32), b: (u32, u32), filled: bool) -> Vec<(u32, u32)> {
+    let (left, top, right, bottom) = bounds(a, b);
+    let centre = ((left + right) as f64 / 2.0, (top + bottom) as f64 / 2.0);
+    let radius = ((right - left) as f64 / 2.0, (bottom - top) as f64 / 2.0);
+    // Walking only the rows leaves gaps where the outline is flat, so walk the columns as well.
+    let mut outline = BTreeSet::new();
+    for y in top..=bottom {
+        let half = half_width(radius.0, radius.1, y as f64 - centre.1);
+        let (start, end) = span(centre.0, half);
+        outline.extend([(y, start), (y, end)]);
+    }
+    for x in left..=right {
+        let half = half_width(radius.1, radius.0, x as f64 - centre.0);
+        let (start, end) = span(centre.1, half);
+        outline.extend([(start, x), (end, x)]);
+    }
+    if !filled {
+        return outline.into_iter().map(|(y, x)| (x, y)).collect();
+    }
+    // Sorted by row, so each row's first and last points are the ends of its span.
+    let mut points = Vec::new();
+    for y in top..=bottom {
+        let mut row = outline.range((y, 0)..=(y, u32::MAX)).map(|&(_, x)| x);
+        if let Some(start) = row.next() {
+            let end = row.next_back().unwrap_or(start);
+            points.extend((start..=end).map(|x| (x, y)));
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut points: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+        points.sort_unstable();
+        points
+    }
+
+    #[test]
+    fn line_reaches_both_ends_in_every_octant() {
+        let start = (5, 5);
+        let ends = [
+            (10, 7),
+            (7, 10),
+            (3, 10),
+            (0, 7),
+            (0, 3),
+            (3, 0),
+            (7, 0),
+            (10, 3),
+        ];
+        for end in ends {
+            let points = line(start, end);
+            assert_eq!(points.first(), Some(&start));
+            assert_eq!(points.last(), Some(&end));
+            let dx = start.0.abs_diff(end.0);
+            let dy = start.1.abs_diff(end.1);
+            assert_eq!(points.len() as u32, dx.max(dy) + 1, "to {end:?}");
+            for pair in points.windows(2) {
+                let step = (pair[0].0.abs_diff(pair[1].0), pair[0].1.abs_diff(pair[1].1));
+                assert!(step.0 <= 1 && step.1 <= 1 && step != (0, 0), "to {end:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn line_to_itself_is_one_point() {
+        assert_eq!(line((3, 4), (3, 4)), [(3, 4)]);
+    }
+
+    #[test]
+    fn rectangle_outline_and_filled() {
+        let outline = sorted(rectangle((4, 3), (1, 1), false));
+        let expected = vec![
+            (1, 1),
+            (1, 2),
+            (1, 3),
+            (2, 1),
+            (2, 3),
+            (3, 1),
+            (3, 3),
+            (4, 1),
+            (4, 2),
+            (4, 3),
+        ];
+        assert_eq!(outline, expected);
+        let filled = sorted(rectangle((1, 1), (4, 3), true));
+        assert_eq!(filled.len(), 12);
+        assert!(filled.contains(&(2, 2)) && filled.contains(&(3, 2)));
+    }
+
+    #[test]
+    fn degenerate_ellipses_are_lines() {
+        for filled in [false, true] {
+            assert_eq!(
+                sorted(ellipse((2, 1), (2, 4), filled)),
+                [(2, 1), (2, 2), (2, 3), (2, 4)]
+            );
+            assert_eq!(
+                sorted(ellipse((5, 3), (1, 3), filled)),
+                [(1, 3), (2, 3), (3, 3), (4, 3), (5, 3)]
+            );
+            assert_eq!(ellipse((6, 6), (6, 6), filled), [(6, 6)]);
+        }
+    }
+
+    #[test]
+    fn ellipse_touches_its_bounds_and_fills_its_outline() {
+        let outline = ellipse((0, 0), (8, 4), false);
+        assert!(outline.contains(&(0, 2)) && outline.contains(&(8, 2)));
+        assert!(outline.contains(&(4, 0)) && outline.contains(&(4, 4)));
+        assert!(!outline.contains(&(4, 2)));
+        let filled = ellipse((0, 0), (8, 4), true);
+        assert!(outline.iter().all(|point| filled.contains(point)));
+        assert!(filled.contains(&(4, 2)));
+        assert!(!filled.contains(&(0, 0)));
+    }
+}
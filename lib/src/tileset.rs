@@ -1,8 +1,10 @@
-use cosmic::iced_core::Hasher;
-use image::{GenericImageView, ImageBuffer, Luma, SubImage};
+use image::{DynamicImage, GenericImageView, ImageBuffer, ImageError, Luma, SubImage};
 use std::fmt;
-use std::hash::{Hash, Hasher as _};
+use std::path::Path;
 use std::sync::Arc;
+use thiserror::Error;
+
+use crate::charmap::CharMap;
 
 const DEFAULT_TILESET_IMAGE: &'static [u8] = include_bytes!("../data/tiles.png");
 
@@ -11,9 +13,117 @@ pub struct Tileset {
     pub id: u64,
     pub image: Arc<ImageBuffer<Luma<u8>, Vec<u8>>>,
     pub tile_size: (u32, u32),
+    /// Which characters the tiles depict, if known.
+    pub charmap: Option<Arc<CharMap>>,
+}
+
+#[derive(Debug, Error)]
+pub enum TilesetError {
+    #[error("couldn't load tileset image: {0}")]
+    Image(#[from] ImageError),
+    #[error("tile size {width}x{height} is invalid")]
+    InvalidTileSize { width: u32, height: u32 },
+    #[error(
+        "image size {width}x{height} isn't a multiple of the tile size {tile_width}x{tile_height}"
+    )]
+    Dimensions {
+        width: u32,
+        height: u32,
+        tile_width: u32,
+        tile_height: u32,
+    },
+}
+
+/// How the pixels of a tileset image are turned into glyph coverage.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Coverage {
+    /// Brightness is coverage, scaled by alpha. Suits white-on-black sheets.
+    #[default]
+    Luma,
+    /// Pixels at least this bright (and at least half opaque) are fully covered, others are empty.
+    Threshold(u8),
+    /// The alpha channel is coverage, colour is ignored. Suits sheets on a transparent background.
+    Alpha,
+}
+
+impl Coverage {
+    fn apply(self, image: &DynamicImage) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+        let image = image.to_luma_alpha8();
+        ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+            let [luma, alpha] = image.get_pixel(x, y).0;
+            let coverage = match self {
+                Coverage::Luma => (luma as u16 * alpha as u16 / u8::MAX as u16) as u8,
+                Coverage::Threshold(threshold) => {
+                    if luma >= threshold && alpha >= 128 {
+                        u8::MAX
+                    } else {
+                        0
+                    }
+                }
+                Coverage::Alpha => alpha,
+            };
+            Luma([coverage])
+        })
+    }
 }
 
 impl Tileset {
+    /// Creates a tileset from a coverage image, checking that it divides evenly into tiles.
+    pub fn new(
+        image: ImageBuffer<Luma<u8>, Vec<u8>>,
+        tile_size: (u32, u32),
+    ) -> Result<Tileset, TilesetError> {
+        if tile_size.0 == 0 || tile_size.1 == 0 {
+            return Err(TilesetError::InvalidTileSize {
+                width: tile_size.0,
+                height: tile_size.1,
+            });
+        }
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 || width % tile_size.0 != 0 || height % tile_size.1 != 0 {
+            return Err(TilesetError::Dimensions {
+                width,
+                height,
+                tile_width: tile_size.0,
+                tile_height: tile_size.1,
+            });
+        }
+        Ok(Tileset {
+            id: tileset_id(&image, tile_size),
+            image: Arc::new(image),
+            tile_size,
+            charmap: None,
+        })
+    }
+
+    pub fn with_charmap(mut self, charmap: CharMap) -> Tileset {
+        self.charmap = Some(Arc::new(charmap));
+        self
+    }
+
+    pub fn from_image(
+        image: &DynamicImage,
+        tile_size: (u32, u32),
+        coverage: Coverage,
+    ) -> Result<Tileset, TilesetError> {
+        Tileset::new(coverage.apply(image), tile_size)
+    }
+
+    /// Loads a tileset from an image file in any format supported by the `image` crate.
+    pub fn from_path(
+        path: impl AsRef<Path>,
+        tile_size: (u32, u32),
+        coverage: Coverage,
+    ) -> Result<Tileset, TilesetError> {
+        let image = image::open(path)?;
+        Tileset::from_image(&image, tile_size, coverage)
+    }
+
+    /// Returns the built-in tileset with the given id, if there is one.
+    pub fn builtin(id: u64) -> Option<Tileset> {
+        Some(Tileset::default()).filter(|tileset| tileset.id == id)
+    }
+
     pub fn tile(&self, index: u32) -> Option<SubImage<&ImageBuffer<Luma<u8>, Vec<u8>>>> {
         let height = self.image.height() / self.tile_size.1;
         let (tile_x, tile_y) = self.tile_position(index);
@@ -29,6 +139,12 @@ impl Tileset {
         }
     }
 
+    /// Whether the tile draws nothing but its background. Tiles outside the tileset are blank.
+    pub fn is_blank(&self, index: u32) -> bool {
+        self.tile(index)
+            .is_none_or(|tile| tile.pixels().all(|(_, _, coverage)| coverage.0[0] == 0))
+    }
+
     pub fn tile_position(&self, index: u32) -> (u32, u32) {
         let width = self.image.width() / self.tile_size.0;
         let tile_x = index % width;
@@ -50,23 +166,51 @@ impl Tileset {
     pub fn height(&self) -> u32 {
         self.image.height() / self.tile_size.1
     }
+
+    pub fn tile_count(&self) -> u32 {
+        self.width() * self.height()
+    }
+
+    /// The character a tile depicts, if the tileset has a character map.
+    pub fn char_for(&self, index: u32) -> Option<char> {
+        self.charmap.as_ref()?.char(index)
+    }
+
+    /// The tile depicting a character, if the tileset has a character map that includes it.
+    pub fn index_for(&self, c: char) -> Option<u32> {
+        self.charmap
+            .as_ref()?
+            .index(c)
+            .filter(|&index| index < self.tile_count())
+    }
 }
 
 impl Default for Tileset {
     fn default() -> Self {
         let image = image::load_from_memory(DEFAULT_TILESET_IMAGE).unwrap();
-        let data = image.into_luma8();
-        let mut hasher = Hasher::default();
-        data.hash(&mut hasher);
-
-        Tileset {
-            id: hasher.finish(),
-            image: Arc::new(data),
-            tile_size: (8, 8),
-        }
+        Tileset::new(image.into_luma8(), (8, 8))
+            .unwrap()
+            .with_charmap(CharMap::ascii())
     }
 }
 
+/// Hashes the tileset contents with FNV-1a.
+///
+/// Ids are stored in saved files, so this must give the same result on every platform and
+/// compiler version, which rules out `std`'s `DefaultHasher`.
+fn tileset_id(image: &ImageBuffer<Luma<u8>, Vec<u8>>, tile_size: (u32, u32)) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let header = [tile_size.0, tile_size.1, image.width(), image.height()];
+    header
+        .iter()
+        .flat_map(|n| n.to_le_bytes())
+        .chain(image.as_raw().iter().copied())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        })
+}
+
 impl fmt::Debug for Tileset {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         f.write_str("<Tileset>")
@@ -1,6 +1,11 @@
-use std::io;
+use std::path::{Path, PathBuf};
 
-use crate::{App, Message};
+use cosmic::app::Command;
+use cosmic::dialog::file_chooser::{self, FileFilter};
+use libmonotile::charmap::CharMap;
+use libmonotile::file_formats::FileType;
+
+use crate::{App, ErrorDialog, Message};
 
 #[derive(Copy, Clone, Hash, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Action {
@@ -8,12 +13,31 @@ pub enum Action {
     Open,
     Save,
     SaveAs,
+    ExportPng,
+    ExportText,
+    ExportAnsiText,
+    ExportHtml,
+    ExportSvg,
+    ImportText,
+    ImportImage,
+    DocumentProperties,
     Quit,
+    LoadTileset,
+    ImportFont,
+    DefaultTileset,
+    CharMap(CharMapPreset),
+    LoadCharMap,
+    CanvasSize,
+    CropToSelection,
+    TrimCanvas,
     Undo,
     Redo,
     Copy,
     Cut,
     Paste,
+    Delete,
+    SelectAll,
+    SelectNone,
     Help,
     About,
     TileUp,
@@ -23,21 +47,58 @@ pub enum Action {
     TileIndex(u32),
 }
 
+/// Character maps built into libmonotile.
+#[derive(Copy, Clone, Hash, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CharMapPreset {
+    Ascii,
+    Cp437,
+    Petscii,
+}
+
+impl CharMapPreset {
+    pub fn charmap(self) -> CharMap {
+        match self {
+            CharMapPreset::Ascii => CharMap::ascii(),
+            CharMapPreset::Cp437 => CharMap::cp437(),
+            CharMapPreset::Petscii => CharMap::petscii(),
+        }
+    }
+}
+
 impl cosmic::widget::menu::action::MenuAction for Action {
     type Message = crate::Message;
 
     fn message(&self) -> Self::Message {
         match self {
-            Action::New => Message::Todo,
-            Action::Open => Message::Todo,
-            Action::Save => Message::Todo,
-            Action::SaveAs => Message::Todo,
+            Action::New => Message::New,
+            Action::Open => Message::Open,
+            Action::Save => Message::Save,
+            Action::SaveAs => Message::SaveAs,
+            Action::ExportPng => Message::ExportPngDialog,
+            Action::ExportText => Message::ExportTextDialog,
+            Action::ExportAnsiText => Message::ExportAnsiTextDialog,
+            Action::ExportHtml => Message::ExportHtmlDialog,
+            Action::ExportSvg => Message::ExportSvgDialog,
+            Action::ImportText => Message::ImportText,
+            Action::ImportImage => Message::ImportImage,
+            Action::DocumentProperties => Message::DocumentProperties,
             Action::Quit => Message::CloseRequested,
-            Action::Undo => Message::Todo,
-            Action::Redo => Message::Todo,
-            Action::Copy => Message::Todo,
-            Action::Cut => Message::Todo,
-            Action::Paste => Message::Todo,
+            Action::LoadTileset => Message::LoadTileset,
+            Action::ImportFont => Message::ImportFont,
+            Action::DefaultTileset => Message::DefaultTileset,
+            Action::CharMap(preset) => Message::SetCharMap(preset.charmap()),
+            Action::LoadCharMap => Message::LoadCharMap,
+            Action::CanvasSize => Message::CanvasSize,
+            Action::CropToSelection => Message::CropToSelection,
+            Action::TrimCanvas => Message::TrimCanvas,
+            Action::Undo => Message::Undo,
+            Action::Redo => Message::Redo,
+            Action::Copy => Message::Copy,
+            Action::Cut => Message::Cut,
+            Action::Paste => Message::Paste,
+            Action::Delete => Message::Delete,
+            Action::SelectAll => Message::SelectAll,
+            Action::SelectNone => Message::SelectNone,
             Action::Help => Message::Todo,
             Action::About => Message::Todo,
             Action::TileUp => Message::TileUp,
@@ -49,46 +110,244 @@ impl cosmic::widget::menu::action::MenuAction for Action {
     }
 }
 
-pub fn save(state: &mut App) -> io::Result<()> {
-    /*
-    if state.file.handle.is_some() {
-        file_formats::save(&state.file)
+pub fn save(state: &mut App) -> Command<Message> {
+    if let Some(path) = state.file.handle.as_ref().map(|handle| handle.path.clone()) {
+        state.save_to(path)
     } else {
         save_dialog(state)
-    }*/
-    unimplemented!()
+    }
 }
 
-fn save_dialog(state: &mut App) -> io::Result<()> {
-    /*
-    let mut dialog = rfd::FileDialog::new().add_filter("Monotile file", &[".monti"]);
-    if let Some(path) = state.file.handle.as_ref().map(|h| &h.path) {
-        dialog = dialog.set_directory(path);
-    }
-    if let Some(name) = state.file.handle.as_ref().map(|h| h.name()) {
-        dialog = dialog.set_file_name(&*name);
-    }
-    if let Some(path) = dialog.save_file() {
-        if state.file.handle.is_none() {
-            state.file.handle = Some(Handle {
-                path,
-                file_type: file_formats::FileType::Monti,
-            });
-        }
-        if let Err(err) = file_formats::save(&state.file) {
-            rfd::MessageDialog::new()
-                .set_level(rfd::MessageLevel::Error)
-                .set_title("Error saving file")
-                .set_description(&format!("Failed to save file: {:?}", err))
-                .show();
-            Err(err)
-        } else {
-            state.file.modified = false;
-            Ok(())
-        }
-    } else {
-        Ok(())
-    }
-    */
-    unimplemented!()
+pub fn save_dialog(state: &App) -> Command<Message> {
+    let name = state
+        .file
+        .handle
+        .as_ref()
+        .map(|handle| handle.name().into_owned());
+    let file_type = state
+        .file
+        .handle
+        .as_ref()
+        .map_or(FileType::Monti, |handle| handle.file_type);
+    save_file_dialog(
+        state,
+        "Save file",
+        vec![
+            FileFilter::new("Monotile file").glob("*.monti"),
+            FileFilter::new("Monotile bundle with tileset").glob("*.montiz"),
+            FileFilter::new("ANSI art").glob("*.ans"),
+            FileFilter::new("XBin").glob("*.xb"),
+            FileFilter::new("REXPaint").glob("*.xp"),
+            FileFilter::new("Tiled map").glob("*.tmx"),
+            FileFilter::new("Tiled map (JSON)").glob("*.tmj"),
+        ],
+        name,
+        file_type.extension(),
+        Message::SaveFile,
+    )
+}
+
+pub fn export_png_dialog(state: &App) -> Command<Message> {
+    export_dialog(
+        state,
+        "Export PNG",
+        FileFilter::new("PNG image").glob("*.png"),
+        "png",
+        Message::ExportPng,
+    )
+}
+
+pub fn export_text_dialog(state: &App) -> Command<Message> {
+    export_dialog(
+        state,
+        "Export text",
+        FileFilter::new("Text file").glob("*.txt"),
+        "txt",
+        Message::ExportText,
+    )
+}
+
+pub fn export_ansi_text_dialog(state: &App) -> Command<Message> {
+    export_dialog(
+        state,
+        "Export text with colours",
+        FileFilter::new("Text file with colour escapes").glob("*.txt"),
+        "txt",
+        Message::ExportAnsiText,
+    )
+}
+
+pub fn export_html_dialog(state: &App) -> Command<Message> {
+    export_dialog(
+        state,
+        "Export HTML",
+        FileFilter::new("HTML page").glob("*.html").glob("*.htm"),
+        "html",
+        Message::ExportHtml,
+    )
+}
+
+pub fn export_svg_dialog(state: &App) -> Command<Message> {
+    export_dialog(
+        state,
+        "Export SVG",
+        FileFilter::new("SVG image").glob("*.svg"),
+        "svg",
+        Message::ExportSvg,
+    )
+}
+
+/// Asks where to export the document, suggesting its name with the given extension.
+fn export_dialog(
+    state: &App,
+    title: &'static str,
+    filter: FileFilter,
+    extension: &'static str,
+    on_chosen: fn(PathBuf) -> Message,
+) -> Command<Message> {
+    let name = state
+        .file
+        .handle
+        .as_ref()
+        .map(|handle| handle.path.with_extension(extension))
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| format!("Untitled.{extension}"));
+    save_file_dialog(state, title, vec![filter], Some(name), extension, on_chosen)
+}
+
+/// Asks where to save, adding `extension` to chosen names that don't have one.
+fn save_file_dialog(
+    state: &App,
+    title: &'static str,
+    filters: Vec<FileFilter>,
+    name: Option<String>,
+    extension: &'static str,
+    on_chosen: fn(PathBuf) -> Message,
+) -> Command<Message> {
+    let directory = state
+        .file
+        .handle
+        .as_ref()
+        .and_then(|handle| handle.path.parent())
+        .map(Path::to_owned);
+    Command::perform(
+        async move {
+            let mut dialog = file_chooser::save::Dialog::new().title(title);
+            for filter in filters {
+                dialog = dialog.filter(filter);
+            }
+            if let Some(directory) = directory {
+                dialog = dialog.directory(directory);
+            }
+            if let Some(name) = name {
+                dialog = dialog.current_name(name);
+            }
+            match dialog.save_file().await {
+                Ok(response) => match response.url().and_then(|url| url.to_file_path().ok()) {
+                    Some(mut path) => {
+                        if path.extension().is_none() {
+                            path.set_extension(extension);
+                        }
+                        on_chosen(path)
+                    }
+                    None => Message::DialogCancelled,
+                },
+                Err(file_chooser::Error::Cancelled) => Message::DialogCancelled,
+                Err(err) => Message::ShowError(ErrorDialog::new("Error saving file", err)),
+            }
+        },
+        cosmic::app::Message::App,
+    )
+}
+
+pub fn open_dialog() -> Command<Message> {
+    open_file_dialog(
+        "Open file",
+        FileFilter::new("Supported files")
+            .glob("*.monti")
+            .glob("*.montiz")
+            .glob("*.ans")
+            .glob("*.xb")
+            .glob("*.xp")
+            .glob("*.tmx")
+            .glob("*.tmj"),
+        Message::OpenFile,
+    )
+}
+
+pub fn import_text_dialog() -> Command<Message> {
+    open_file_dialog(
+        "Import text",
+        FileFilter::new("Text files").glob("*.txt"),
+        Message::ImportTextFile,
+    )
+}
+
+pub fn import_image_dialog() -> Command<Message> {
+    open_file_dialog("Import image", image_filter(), Message::ImportImageFile)
+}
+
+pub fn open_tileset_dialog() -> Command<Message> {
+    open_file_dialog(
+        "Open tileset image",
+        image_filter(),
+        Message::TilesetFileChosen,
+    )
+}
+
+fn image_filter() -> FileFilter {
+    FileFilter::new("Images")
+        .glob("*.png")
+        .glob("*.jpg")
+        .glob("*.jpeg")
+        .glob("*.bmp")
+        .glob("*.gif")
+        .glob("*.tga")
+}
+
+pub fn open_font_dialog() -> Command<Message> {
+    open_file_dialog(
+        "Import font",
+        FileFilter::new("Bitmap fonts")
+            .glob("*.psf")
+            .glob("*.psf.gz")
+            .glob("*.psfu")
+            .glob("*.psfu.gz")
+            .glob("*.bdf")
+            .glob("*.bdf.gz"),
+        Message::FontFileChosen,
+    )
+}
+
+pub fn open_charmap_dialog() -> Command<Message> {
+    open_file_dialog(
+        "Load character map",
+        FileFilter::new("Mapping tables")
+            .glob("*.txt")
+            .glob("*.TXT"),
+        Message::CharMapFileChosen,
+    )
+}
+
+fn open_file_dialog(
+    title: &'static str,
+    filter: FileFilter,
+    on_chosen: fn(PathBuf) -> Message,
+) -> Command<Message> {
+    Command::perform(
+        async move {
+            let dialog = file_chooser::open::Dialog::new()
+                .title(title)
+                .filter(filter);
+            match dialog.open_file().await {
+                Ok(response) => match response.url().to_file_path() {
+                    Ok(path) => on_chosen(path),
+                    Err(()) => Message::DialogCancelled,
+                },
+                Err(file_chooser::Error::Cancelled) => Message::DialogCancelled,
+                Err(err) => Message::ShowError(ErrorDialog::new("Error opening file", err)),
+            }
+        },
+        cosmic::app::Message::App,
+    )
 }
@@ -0,0 +1,265 @@
+//! Subcommands that work on files directly, without opening a window.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Subcommand};
+use image::ImageFormat;
+use libmonotile::canvas::{Color, Tile};
+use libmonotile::file_formats::{self, html, svg, text, FileType, Handle};
+use libmonotile::file_state::FileState;
+use libmonotile::font::{self, FontError};
+use libmonotile::render;
+use libmonotile::tileset::{Coverage, Tileset};
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Converts a file to the format given by the output's extension.
+    ///
+    /// Besides the formats monotile can open, the output can be a .png, .svg, .html or .txt
+    /// export.
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        #[command(flatten)]
+        tileset: TilesetArgs,
+    },
+    /// Renders a file to an image.
+    Render {
+        input: PathBuf,
+        output: PathBuf,
+        /// Size of each tileset pixel in the image.
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        scale: u32,
+        #[command(flatten)]
+        tileset: TilesetArgs,
+    },
+    /// Prints a file's dimensions and how often each tile and colour is used.
+    Info { path: PathBuf },
+}
+
+#[derive(Args, Debug)]
+pub struct TilesetArgs {
+    /// Tileset image or PSF/BDF font to use instead of the file's own tileset.
+    #[arg(long)]
+    tileset: Option<PathBuf>,
+    /// Size of the tiles in a tileset image, as WIDTHxHEIGHT.
+    #[arg(long, default_value = "8x8", value_parser = parse_size)]
+    tile_size: (u32, u32),
+}
+
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected WIDTHxHEIGHT, got {size:?}");
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let width = width.parse().map_err(|_| invalid())?;
+    let height = height.parse().map_err(|_| invalid())?;
+    Ok((width, height))
+}
+
+impl TilesetArgs {
+    /// Loads the file, switching to the tileset given on the command line if there is one.
+    fn load(&self, path: &Path) -> Result<FileState, Box<dyn Error>> {
+        let mut file = file_formats::load(path)?;
+        if let Some(tileset) = &self.tileset {
+            file.tileset = load_tileset(tileset, self.tile_size)?;
+        }
+        Ok(file)
+    }
+}
+
+/// Loads a font if the file is one, and treats it as a tileset image otherwise.
+fn load_tileset(path: &Path, tile_size: (u32, u32)) -> Result<Tileset, Box<dyn Error>> {
+    let data = fs::read(path)?;
+    match font::parse(&data) {
+        Ok(font) => Ok(font.into_tileset()?),
+        Err(FontError::UnknownFormat) => {
+            let image = image::load_from_memory(&data)?;
+            Ok(Tileset::from_image(&image, tile_size, Coverage::default())?)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Convert {
+            input,
+            output,
+            tileset,
+        } => convert(tileset.load(&input)?, output),
+        Command::Render {
+            input,
+            output,
+            scale,
+            tileset,
+        } => {
+            let file = tileset.load(&input)?;
+            let image = render::render(&file.canvas, &file.tileset, scale)?;
+            image.save_with_format(output, ImageFormat::Png)?;
+            Ok(())
+        }
+        Command::Info { path } => info(&path),
+    }
+}
+
+fn convert(mut file: FileState, output: PathBuf) -> Result<(), Box<dyn Error>> {
+    let extension = output
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let (canvas, tileset) = (&file.canvas, &file.tileset);
+    let exported = match extension.as_deref() {
+        Some("png") => {
+            let image = render::render(canvas, tileset, 1)?;
+            image.save_with_format(&output, ImageFormat::Png)?;
+            return Ok(());
+        }
+        Some("svg") => svg::to_svg(canvas, tileset),
+        Some("html" | "htm") => {
+            let title = output.file_stem().unwrap_or_default().to_string_lossy();
+            html::to_html(canvas, tileset, &title)
+        }
+        Some("txt") => text::to_text(canvas, tileset),
+        _ => {
+            // Anything else falls back to `.monti`, which would be misleading under another name.
+            let file_type = FileType::from_path(&output);
+            match extension.as_deref() {
+                Some(ext) if ext == file_type.extension() => {}
+                Some(ext) => return Err(format!("unsupported output format .{ext}").into()),
+                None => return Err("output file has no extension to pick a format from".into()),
+            }
+            file.handle = Some(Handle {
+                file_type,
+                path: output,
+            });
+            file_formats::save(&file)?;
+            return Ok(());
+        }
+    };
+    fs::write(output, exported)?;
+    Ok(())
+}
+
+/// Counts of each key, most common first, ties in key order.
+fn histogram<K: Hash + Ord + Copy>(keys: impl Iterator<Item = K>) -> Vec<(K, usize)> {
+    let mut counts = HashMap::new();
+    for key in keys {
+        *counts.entry(key).or_insert(0) += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+fn info(path: &Path) -> Result<(), Box<dyn Error>> {
+    let file = file_formats::load(path)?;
+    let (canvas, tileset) = (&file.canvas, &file.tileset);
+    let (tile_width, tile_height) = tileset.tile_size;
+    println!("File: {}", path.display());
+    println!("Size: {}x{} tiles", canvas.width, canvas.height);
+    println!(
+        "Tileset: {} tiles of {tile_width}x{tile_height} pixels",
+        tileset.tile_count()
+    );
+    if let Some(sauce) = &file.sauce {
+        for (name, value) in [
+            ("Title", &sauce.title),
+            ("Author", &sauce.author),
+            ("Group", &sauce.group),
+        ] {
+            if !value.is_empty() {
+                println!("{name}: {value}");
+            }
+        }
+    }
+
+    println!("\nTiles:");
+    for (index, count) in histogram(canvas.tiles().map(|(_, _, tile)| tile.index)) {
+        match tileset.char_for(index) {
+            Some(c) if !c.is_control() => println!("{count:>8}  {index:>5}  {c}"),
+            _ => println!("{count:>8}  {index:>5}"),
+        }
+    }
+    // Colours are counted by their packed value, as `Color` isn't hashable.
+    let colors = |color: fn(&Tile) -> Color| {
+        histogram(canvas.tiles().map(|(_, _, tile)| color(tile).to_argb()))
+    };
+    for (name, counts) in [
+        ("Foreground colours", colors(|tile| tile.fg)),
+        ("Background colours", colors(|tile| tile.bg)),
+    ] {
+        println!("\n{name}:");
+        for (argb, count) in counts {
+            let color = Color::rgb((argb >> 16) as u8, (argb >> 8) as u8, argb as u8);
+            println!("{count:>8}  {}", color.to_hex());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libmonotile::canvas::Canvas;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("monotile-cli-{}-{name}", std::process::id()))
+    }
+
+    fn document() -> FileState {
+        let mut canvas = Canvas::new(3, 1);
+        for (x, c) in "hi!".chars().enumerate() {
+            let tile = Tile {
+                index: c as u32,
+                ..Tile::default()
+            };
+            canvas.set_tile(x as u32, 0, tile);
+        }
+        FileState {
+            canvas,
+            ..FileState::default()
+        }
+    }
+
+    #[test]
+    fn parses_tile_sizes() {
+        assert_eq!(parse_size("8x16"), Ok((8, 16)));
+        assert!(parse_size("8").is_err());
+        assert!(parse_size("8xa").is_err());
+    }
+
+    #[test]
+    fn histogram_puts_most_common_first() {
+        let counts = histogram([3, 1, 2, 1, 3, 3].into_iter());
+        assert_eq!(counts, [(3, 3), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn convert_rejects_unknown_extensions() {
+        for name in ["out.gif", "out"] {
+            let path = temp_path(name);
+            let err = convert(document(), path.clone()).unwrap_err();
+            assert!(!path.exists(), "{name}");
+            if name == "out.gif" {
+                assert!(err.to_string().contains(".gif"), "{err}");
+            }
+        }
+    }
+
+    #[test]
+    fn convert_writes_by_extension() {
+        let path = temp_path("out.txt");
+        convert(document(), path.clone()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().trim_end(), "hi!");
+        fs::remove_file(path).unwrap();
+
+        let path = temp_path("out.MONTI");
+        convert(document(), path.clone()).unwrap();
+        let loaded = file_formats::load(&path).unwrap();
+        assert_eq!(loaded.canvas, document().canvas);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+//! Tiles on the system clipboard, in our own format for pasting back into monotile and as text
+//! and images for other programs.
+
+use std::borrow::Cow;
+use std::io::Cursor;
+
+use cosmic::iced::clipboard::mime::{AllowedMimeTypes, AsMimeTypes};
+use image::ImageFormat;
+use libmonotile::canvas::Canvas;
+use libmonotile::file_formats::{text, SaveData};
+use libmonotile::render;
+use libmonotile::tileset::Tileset;
+
+/// Tiles with their indices and colours, as a `.monti` document.
+const TILES: &str = "application/x-monotile";
+/// Text with colour escape sequences, for terminals.
+const ANSI_TEXT: &str = "text/x-ansi";
+const PNG: &str = "image/png";
+/// Names plain text goes by, UTF-8 first.
+const TEXT: [&str; 3] = ["text/plain;charset=utf-8", "UTF8_STRING", "text/plain"];
+
+/// Copied tiles, converted up front as the clipboard may ask for any of the types later.
+pub struct ClipboardCopy {
+    available: Vec<String>,
+    tiles: Vec<u8>,
+    text: String,
+    ansi_text: String,
+    png: Vec<u8>,
+}
+
+impl ClipboardCopy {
+    pub fn new(tiles: &Canvas, tileset: &Tileset) -> ClipboardCopy {
+        let mut data = Vec::new();
+        let mut png = Vec::new();
+        let mut available = Vec::new();
+        if SaveData::from_canvas(tiles).to_writer(&mut data).is_ok() {
+            available.push(TILES.to_owned());
+        }
+        let image = render::render(tiles, tileset, 1);
+        if image.is_ok_and(|image| {
+            image
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .is_ok()
+        }) {
+            available.push(PNG.to_owned());
+        }
+        available.push(ANSI_TEXT.to_owned());
+        available.extend(TEXT.map(String::from));
+        ClipboardCopy {
+            available,
+            tiles: data,
+            text: text::to_text(tiles, tileset),
+            ansi_text: text::to_ansi_text(tiles, tileset),
+            png,
+        }
+    }
+}
+
+impl AsMimeTypes for ClipboardCopy {
+    fn available(&self) -> Cow<'static, [String]> {
+        Cow::Owned(self.available.clone())
+    }
+
+    fn as_bytes(&self, mime_type: &str) -> Option<Cow<'static, [u8]>> {
+        let bytes = match mime_type {
+            TILES => self.tiles.clone(),
+            PNG => self.png.clone(),
+            ANSI_TEXT => self.ansi_text.clone().into_bytes(),
+            _ if TEXT.contains(&mime_type) => self.text.clone().into_bytes(),
+            _ => return None,
+        };
+        Some(Cow::Owned(bytes))
+    }
+}
+
+/// What can be pasted from the clipboard: our own tiles, or text to turn into tiles.
+#[derive(Clone, Debug)]
+pub enum ClipboardPaste {
+    Tiles(Canvas),
+    Text(String),
+}
+
+impl AllowedMimeTypes for ClipboardPaste {
+    fn allowed() -> Cow<'static, [String]> {
+        let mut allowed = vec![TILES.to_owned()];
+        allowed.extend(TEXT.map(String::from));
+        Cow::Owned(allowed)
+    }
+}
+
+impl TryFrom<(Vec<u8>, String)> for ClipboardPaste {
+    type Error = ();
+
+    fn try_from((data, mime_type): (Vec<u8>, String)) -> Result<Self, Self::Error> {
+        if mime_type == TILES {
+            let data = SaveData::from_reader(data.as_slice()).map_err(|_| ())?;
+            return data.to_canvas().map(ClipboardPaste::Tiles).map_err(|_| ());
+        }
+        String::from_utf8(data)
+            .map(ClipboardPaste::Text)
+            .map_err(|_| ())
+    }
+}
@@ -0,0 +1,265 @@
+use cosmic::iced_widget::{column, row, Column, Row};
+use cosmic::widget::{self, spin_button, text};
+use cosmic::{Apply, Element};
+use libmonotile::canvas::Anchor;
+use libmonotile::convert::{ConvertOptions, Metric};
+use libmonotile::file_formats::ansi;
+use libmonotile::sauce::{self, Sauce};
+use libmonotile::tileset::Coverage;
+
+use crate::Message;
+
+static COVERAGE_NAMES: [&str; 3] = ["Brightness", "Brightness threshold", "Alpha"];
+const COVERAGES: [Coverage; 3] = [Coverage::Luma, Coverage::Threshold(128), Coverage::Alpha];
+
+static METRIC_NAMES: [&str; 3] = ["Colour", "Colour, tolerant", "Brightness"];
+const METRICS: [Metric; 3] = [Metric::Squared, Metric::Absolute, Metric::Luma];
+static PALETTE_NAMES: [&str; 2] = ["Any colour", "16 VGA colours"];
+
+/// Settings for turning a picture into tiles, chosen before picking the file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageImportOptions {
+    /// Width of the canvas in tiles.
+    pub width: u32,
+    pub metric: Metric,
+    pub vga_palette: bool,
+}
+
+impl Default for ImageImportOptions {
+    fn default() -> Self {
+        ImageImportOptions {
+            width: 80,
+            metric: Metric::default(),
+            vga_palette: false,
+        }
+    }
+}
+
+impl ImageImportOptions {
+    pub fn convert_options(self) -> ConvertOptions {
+        ConvertOptions {
+            width: Some(self.width),
+            metric: self.metric,
+            palette: self.vga_palette.then(|| ansi::PALETTE.to_vec()),
+        }
+    }
+}
+
+/// Settings for loading a tileset image, chosen before picking the file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TilesetOptions {
+    pub tile_size: (u32, u32),
+    pub coverage: Coverage,
+}
+
+impl Default for TilesetOptions {
+    fn default() -> Self {
+        TilesetOptions {
+            tile_size: (8, 8),
+            coverage: Coverage::default(),
+        }
+    }
+}
+
+pub fn tileset_options(options: TilesetOptions) -> Element<'static, Message> {
+    let size_button = move |label: &str, value: u32, set: fn(&mut TilesetOptions, u32)| {
+        spin_button(format!("{label}: {value}"), move |message| {
+            let mut options = options;
+            let value = match message {
+                spin_button::Message::Increment => (value + 1).min(64),
+                spin_button::Message::Decrement => (value - 1).max(1),
+            };
+            set(&mut options, value);
+            Message::TilesetOptionsChanged(options)
+        })
+    };
+    let selected = COVERAGES
+        .iter()
+        .position(|&coverage| coverage == options.coverage);
+    let controls = column![
+        row![
+            size_button("Tile width", options.tile_size.0, |options, width| {
+                options.tile_size.0 = width
+            }),
+            size_button("Tile height", options.tile_size.1, |options, height| {
+                options.tile_size.1 = height
+            }),
+        ]
+        .spacing(8),
+        row![
+            text("Glyph shape from"),
+            widget::dropdown(&COVERAGE_NAMES, selected, move |index| {
+                Message::TilesetOptionsChanged(TilesetOptions {
+                    coverage: COVERAGES[index],
+                    ..options
+                })
+            }),
+        ]
+        .spacing(8),
+    ]
+    .spacing(8);
+    widget::dialog("Load tileset")
+        .body("The image is split into tiles of this size, left to right and top to bottom.")
+        .control(controls)
+        .primary_action(
+            widget::button::suggested("Choose image").on_press(Message::ChooseTilesetFile),
+        )
+        .secondary_action(widget::button::standard("Cancel").on_press(Message::CloseDialog))
+        .apply(Element::from)
+}
+
+/// New size for the canvas, and which side the existing tiles stay on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CanvasSizeOptions {
+    pub width: u32,
+    pub height: u32,
+    pub anchor: Anchor,
+}
+
+const ANCHOR_LABELS: [&str; 9] = ["↖", "↑", "↗", "←", "•", "→", "↙", "↓", "↘"];
+
+pub fn canvas_size(options: CanvasSizeOptions) -> Element<'static, Message> {
+    let size_button = move |label: &str, value: u32, set: fn(&mut CanvasSizeOptions, u32)| {
+        spin_button(format!("{label}: {value}"), move |message| {
+            let mut options = options;
+            let value = match message {
+                // Canvases already past the limit can't grow, but mustn't shrink either.
+                spin_button::Message::Increment => (value + 1).min(value.max(1000)),
+                spin_button::Message::Decrement => (value - 1).max(1),
+            };
+            set(&mut options, value);
+            Message::CanvasSizeChanged(options)
+        })
+    };
+    let anchor_button = move |index: usize| {
+        let anchor = Anchor::ALL[index];
+        let button = if anchor == options.anchor {
+            widget::button::suggested(ANCHOR_LABELS[index])
+        } else {
+            widget::button::standard(ANCHOR_LABELS[index])
+        };
+        button.on_press(Message::CanvasSizeChanged(CanvasSizeOptions {
+            anchor,
+            ..options
+        }))
+    };
+    let anchors = Column::with_children((0..3).map(|y| {
+        Row::with_children((0..3).map(|x| anchor_button(y * 3 + x).into()))
+            .spacing(4)
+            .into()
+    }))
+    .spacing(4);
+    let controls = column![
+        row![
+            size_button("Width", options.width, |options, width| {
+                options.width = width
+            }),
+            size_button("Height", options.height, |options, height| {
+                options.height = height
+            }),
+        ]
+        .spacing(8),
+        row![text("Keep tiles at"), anchors].spacing(8),
+    ]
+    .spacing(8);
+    widget::dialog("Canvas size")
+        .body("New space is filled with blank tiles in the current background colour.")
+        .control(controls)
+        .primary_action(widget::button::suggested("Resize").on_press(Message::ApplyCanvasSize))
+        .secondary_action(widget::button::standard("Cancel").on_press(Message::CloseDialog))
+        .apply(Element::from)
+}
+
+pub fn image_import_options(options: ImageImportOptions) -> Element<'static, Message> {
+    let width = spin_button(
+        format!("Width in tiles: {}", options.width),
+        move |message| {
+            let width = match message {
+                spin_button::Message::Increment => (options.width + 1).min(1000),
+                spin_button::Message::Decrement => (options.width - 1).max(1),
+            };
+            Message::ImageImportOptionsChanged(ImageImportOptions { width, ..options })
+        },
+    );
+    let metric = METRICS.iter().position(|&metric| metric == options.metric);
+    let palette = Some(options.vga_palette as usize);
+    let controls = column![
+        width,
+        row![
+            text("Match by"),
+            widget::dropdown(&METRIC_NAMES, metric, move |index| {
+                Message::ImageImportOptionsChanged(ImageImportOptions {
+                    metric: METRICS[index],
+                    ..options
+                })
+            }),
+        ]
+        .spacing(8),
+        row![
+            text("Colours"),
+            widget::dropdown(&PALETTE_NAMES, palette, move |index| {
+                Message::ImageImportOptionsChanged(ImageImportOptions {
+                    vga_palette: index == 1,
+                    ..options
+                })
+            }),
+        ]
+        .spacing(8),
+    ]
+    .spacing(8);
+    widget::dialog("Import image")
+        .body("Each cell of the picture becomes the tile and colours that look most like it.")
+        .control(controls)
+        .primary_action(
+            widget::button::suggested("Choose image").on_press(Message::ChooseImportImage),
+        )
+        .secondary_action(widget::button::standard("Cancel").on_press(Message::CloseDialog))
+        .apply(Element::from)
+}
+
+/// Editor for the SAUCE fields people fill in. The rest of the record describes the canvas and
+/// is filled in when saving.
+pub fn document_properties(sauce: &Sauce) -> Element<'_, Message> {
+    let field = |label: &'static str, value: &str, max_len: usize, set: fn(&mut Sauce, String)| {
+        let sauce = sauce.clone();
+        column![
+            text(label),
+            widget::text_input("", value.to_owned()).on_input(move |value| {
+                let mut sauce = sauce.clone();
+                set(&mut sauce, value.chars().take(max_len).collect());
+                Message::PropertiesChanged(sauce)
+            }),
+        ]
+        .spacing(4)
+    };
+    let controls = column![
+        field("Title", &sauce.title, sauce::TITLE_LEN, |sauce, title| {
+            sauce.title = title
+        }),
+        field(
+            "Author",
+            &sauce.author,
+            sauce::AUTHOR_LEN,
+            |sauce, author| { sauce.author = author }
+        ),
+        field("Group", &sauce.group, sauce::GROUP_LEN, |sauce, group| {
+            sauce.group = group
+        }),
+        field(
+            "Date (YYYYMMDD)",
+            &sauce.date,
+            sauce::DATE_LEN,
+            |sauce, date| { sauce.date = date }
+        ),
+        field("Font", &sauce.font, sauce::FONT_LEN, |sauce, font| {
+            sauce.font = font
+        }),
+    ]
+    .spacing(8);
+    widget::dialog("Document properties")
+        .body("Saved as a SAUCE record in ANSI and XBin files.")
+        .control(controls)
+        .primary_action(widget::button::suggested("Apply").on_press(Message::ApplyProperties))
+        .secondary_action(widget::button::standard("Cancel").on_press(Message::CloseDialog))
+        .apply(Element::from)
+}
@@ -1,5 +1,5 @@
 use cosmic::{
-    iced_core::keyboard::Key,
+    iced_core::keyboard::{key::Named, Key},
     widget::menu::key_bind::{KeyBind, Modifier},
 };
 use std::collections::HashMap;
@@ -15,6 +15,27 @@ pub fn key_binds() -> HashMap<KeyBind, Action> {
             },
             Action::New,
         ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("o".into()),
+            },
+            Action::Open,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("s".into()),
+            },
+            Action::Save,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl, Modifier::Shift],
+                key: Key::Character("s".into()),
+            },
+            Action::SaveAs,
+        ),
         (
             KeyBind {
                 modifiers: vec![Modifier::Ctrl],
@@ -22,6 +43,62 @@ pub fn key_binds() -> HashMap<KeyBind, Action> {
             },
             Action::Quit,
         ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("z".into()),
+            },
+            Action::Undo,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl, Modifier::Shift],
+                key: Key::Character("z".into()),
+            },
+            Action::Redo,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("x".into()),
+            },
+            Action::Cut,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("c".into()),
+            },
+            Action::Copy,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("v".into()),
+            },
+            Action::Paste,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![],
+                key: Key::Named(Named::Delete),
+            },
+            Action::Delete,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("a".into()),
+            },
+            Action::SelectAll,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![],
+                key: Key::Named(Named::Escape),
+            },
+            Action::SelectNone,
+        ),
         (
             KeyBind {
                 modifiers: vec![],
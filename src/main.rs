@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use clap::Parser;
 use cosmic::app::{Command, Core, Settings};
@@ -10,28 +13,50 @@ use cosmic::iced_widget::{column, row, scrollable};
 use cosmic::widget::menu::{action::MenuAction, key_bind::KeyBind};
 use cosmic::widget::{self, container, slider, spin_button, text};
 use cosmic::{ApplicationExt, Apply, Element};
-use libmonotile::canvas::Tile;
+use image::ImageFormat;
+use libmonotile::canvas::{self, Anchor, Canvas, FillOptions, Region, Tile, TilePart};
+use libmonotile::charmap::CharMap;
+use libmonotile::convert;
+use libmonotile::file_formats::{self, html, svg, text, FileType, Handle};
 use libmonotile::file_state::FileState;
+use libmonotile::font;
+use libmonotile::render;
+use libmonotile::sauce::Sauce;
+use libmonotile::shape::Shape;
 use libmonotile::tileset::Tileset;
 
 mod actions;
+mod cli;
+mod clipboard;
+mod dialogs;
 mod key_binds;
 mod menu;
 mod tile_canvas;
 mod tile_selector;
+mod tool_palette;
 
 pub use actions::Action;
 
-use tile_canvas::{tile_canvas, State};
+use clipboard::{ClipboardCopy, ClipboardPaste};
+use dialogs::{CanvasSizeOptions, ImageImportOptions, TilesetOptions};
+use tile_canvas::{tile_canvas, Floating};
 use tile_selector::tile_selector;
+use tool_palette::tool_palette;
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+    /// File to open in the editor.
     path: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(command) = args.command.take() {
+        return cli::run(command);
+    }
     cosmic::app::run::<App>(
         Settings::default()
             .client_decorations(true)
@@ -44,10 +69,22 @@ fn main() -> Result<(), Box<dyn Error>> {
 struct App {
     core: Core,
     current_tool: Tool,
+    fill_options: FillOptions,
+    pick_mode: TilePart,
     current_tile: Tile,
-    filename: Option<String>,
-    quit_confirmation_dialog: bool,
-    tileset: Tileset,
+    confirmation_dialog: Option<PendingAction>,
+    after_save: Option<PendingAction>,
+    error_dialog: Option<ErrorDialog>,
+    tileset_dialog: Option<TilesetOptions>,
+    properties_dialog: Option<Sauce>,
+    image_import_dialog: Option<ImageImportOptions>,
+    image_import_options: ImageImportOptions,
+    canvas_size_dialog: Option<CanvasSizeOptions>,
+    selection: Option<Region>,
+    floating: Option<Floating>,
+    /// Tiles copied within the editor, with their full colours and indices.
+    clipboard: Option<Canvas>,
+    tileset_options: TilesetOptions,
     key_binds: HashMap<KeyBind, Action>,
     modifiers: Modifiers,
     file: libmonotile::file_state::FileState,
@@ -60,6 +97,65 @@ pub enum Message {
     Open,
     Save,
     SaveAs,
+    OpenFile(PathBuf),
+    SaveFile(PathBuf),
+    ExportPngDialog,
+    ExportPng(PathBuf),
+    ExportTextDialog,
+    ExportText(PathBuf),
+    ExportAnsiTextDialog,
+    ExportAnsiText(PathBuf),
+    ExportHtmlDialog,
+    ExportHtml(PathBuf),
+    ExportSvgDialog,
+    ExportSvg(PathBuf),
+    ImportText,
+    ImportTextFile(PathBuf),
+    ImportImage,
+    ImageImportOptionsChanged(ImageImportOptions),
+    ChooseImportImage,
+    ImportImageFile(PathBuf),
+    DialogCancelled,
+    ShowError(ErrorDialog),
+    DismissError,
+    ConfirmSave,
+    ConfirmDiscard,
+    ConfirmCancel,
+    CloseDialog,
+    DocumentProperties,
+    PropertiesChanged(Sauce),
+    ApplyProperties,
+    LoadTileset,
+    ImportFont,
+    DefaultTileset,
+    TilesetOptionsChanged(TilesetOptions),
+    ChooseTilesetFile,
+    TilesetFileChosen(PathBuf),
+    FontFileChosen(PathBuf),
+    SetCharMap(CharMap),
+    LoadCharMap,
+    CharMapFileChosen(PathBuf),
+    CanvasSize,
+    CanvasSizeChanged(CanvasSizeOptions),
+    ApplyCanvasSize,
+    TrimCanvas,
+    CropToSelection,
+    SelectTool(Tool),
+    FillOptionsChanged(FillOptions),
+    PickModeChanged(TilePart),
+    SelectionChanged(Option<Region>),
+    MoveFloating {
+        x: i32,
+        y: i32,
+    },
+    CommitFloating,
+    Cut,
+    Copy,
+    Paste,
+    PasteContents(Option<ClipboardPaste>),
+    Delete,
+    SelectAll,
+    SelectNone,
     CloseRequested,
     Key(Modifiers, Key),
     ModifiersChanged(Modifiers),
@@ -70,7 +166,23 @@ pub enum Message {
     TileRight,
     ZoomIn,
     ZoomOut,
-    CanvasClicked { x: u32, y: u32 },
+    CanvasClicked {
+        x: u32,
+        y: u32,
+    },
+    PickTile {
+        x: u32,
+        y: u32,
+    },
+    StrokeStarted,
+    StrokeFinished,
+    DrawShape {
+        shape: Shape,
+        start: (u32, u32),
+        end: (u32, u32),
+    },
+    Undo,
+    Redo,
     Todo,
 }
 
@@ -81,16 +193,34 @@ impl cosmic::Application for App {
     type Message = Message;
 
     fn init(core: Core, flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        let (file, error_dialog) = match FileState::new(flags.path.as_ref()) {
+            Ok(file) => (file, None),
+            Err(err) => (
+                FileState::default(),
+                Some(ErrorDialog::new("Error opening file", err)),
+            ),
+        };
         let mut app = App {
             core,
             current_tool: Tool::Draw,
+            fill_options: FillOptions::default(),
+            pick_mode: TilePart::default(),
             current_tile: Tile::default(),
-            filename: None,
-            quit_confirmation_dialog: false,
-            tileset: Tileset::default(),
+            confirmation_dialog: None,
+            after_save: None,
+            error_dialog,
+            tileset_dialog: None,
+            properties_dialog: None,
+            image_import_dialog: None,
+            image_import_options: ImageImportOptions::default(),
+            canvas_size_dialog: None,
+            selection: None,
+            floating: None,
+            clipboard: None,
+            tileset_options: TilesetOptions::default(),
             key_binds: key_binds::key_binds(),
             modifiers: Modifiers::empty(),
-            file: FileState::new(flags.path).unwrap(),
+            file,
             zoom: 2,
         };
         let command = app.update_title();
@@ -99,17 +229,282 @@ impl cosmic::Application for App {
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
-            Message::New => return self.update(Message::Todo),
-            Message::Open => return self.update(Message::Todo),
-            Message::Save => return self.update(Message::Todo),
-            Message::SaveAs => return self.update(Message::Todo),
-            Message::CloseRequested => {
-                if self.file.modified {
-                    self.quit_confirmation_dialog = true;
-                } else {
-                    return iced::window::close(self.main_window_id());
+            Message::New => return self.confirm(PendingAction::New),
+            Message::Open => return self.confirm(PendingAction::Open),
+            Message::Save => return actions::save(self),
+            Message::SaveAs => return actions::save_dialog(self),
+            Message::OpenFile(path) => match file_formats::load(&path) {
+                Ok(file) => return self.set_file(file),
+                Err(err) => {
+                    self.error_dialog = Some(ErrorDialog::new("Error opening file", err));
+                }
+            },
+            Message::SaveFile(path) => return self.save_to(path),
+            Message::ExportPngDialog => return actions::export_png_dialog(self),
+            Message::ExportPng(path) => {
+                match render::render(&self.file.canvas, &self.file.tileset, 1) {
+                    Ok(image) => {
+                        if let Err(err) = image.save_with_format(path, ImageFormat::Png) {
+                            self.error_dialog = Some(ErrorDialog::new("Error exporting PNG", err));
+                        }
+                    }
+                    Err(err) => {
+                        self.error_dialog = Some(ErrorDialog::new("Error exporting PNG", err));
+                    }
+                }
+            }
+            Message::ExportTextDialog => return actions::export_text_dialog(self),
+            Message::ExportText(path) => {
+                self.export_to(path, text::to_text(&self.file.canvas, &self.file.tileset));
+            }
+            Message::ExportAnsiTextDialog => return actions::export_ansi_text_dialog(self),
+            Message::ExportAnsiText(path) => {
+                let text = text::to_ansi_text(&self.file.canvas, &self.file.tileset);
+                self.export_to(path, text);
+            }
+            Message::ExportHtmlDialog => return actions::export_html_dialog(self),
+            Message::ExportHtml(path) => {
+                let title = self.file_name();
+                let html = html::to_html(&self.file.canvas, &self.file.tileset, &title);
+                self.export_to(path, html);
+            }
+            Message::ExportSvgDialog => return actions::export_svg_dialog(self),
+            Message::ExportSvg(path) => {
+                self.export_to(path, svg::to_svg(&self.file.canvas, &self.file.tileset));
+            }
+            Message::ImportText => return self.confirm(PendingAction::ImportText),
+            Message::ImportTextFile(path) => match fs::read_to_string(path) {
+                Ok(contents) => {
+                    let tileset = self.file.tileset.clone();
+                    let (fg, bg) = (self.current_tile.fg, self.current_tile.bg);
+                    let file = FileState {
+                        canvas: text::from_text(&contents, &tileset, fg, bg),
+                        tileset,
+                        // Never saved, so closing it should ask first.
+                        modified: true,
+                        ..FileState::default()
+                    };
+                    return self.set_file(file);
+                }
+                Err(err) => {
+                    self.error_dialog = Some(ErrorDialog::new("Error importing text", err));
+                }
+            },
+            Message::ImportImage => return self.confirm(PendingAction::ImportImage),
+            Message::ImageImportOptionsChanged(options) => {
+                self.image_import_dialog = Some(options);
+            }
+            Message::ChooseImportImage => {
+                if let Some(options) = self.image_import_dialog.take() {
+                    self.image_import_options = options;
+                    return actions::import_image_dialog();
+                }
+            }
+            Message::ImportImageFile(path) => match image::open(path) {
+                Ok(image) => {
+                    let tileset = self.file.tileset.clone();
+                    let options = self.image_import_options.convert_options();
+                    match convert::image_to_canvas(&image.to_rgb8(), &tileset, &options) {
+                        Ok(canvas) => {
+                            let file = FileState {
+                                canvas,
+                                tileset,
+                                modified: true,
+                                ..FileState::default()
+                            };
+                            return self.set_file(file);
+                        }
+                        Err(err) => {
+                            self.error_dialog =
+                                Some(ErrorDialog::new("Error importing image", err));
+                        }
+                    }
+                }
+                Err(err) => {
+                    self.error_dialog = Some(ErrorDialog::new("Error importing image", err));
+                }
+            },
+            Message::DialogCancelled => {
+                self.after_save = None;
+            }
+            Message::ShowError(error) => {
+                self.after_save = None;
+                self.error_dialog = Some(error);
+            }
+            Message::DismissError => {
+                self.error_dialog = None;
+            }
+            Message::ConfirmSave => {
+                self.after_save = self.confirmation_dialog.take();
+                return actions::save(self);
+            }
+            Message::ConfirmDiscard => {
+                if let Some(action) = self.confirmation_dialog.take() {
+                    return self.perform(action);
+                }
+            }
+            Message::ConfirmCancel => {
+                self.confirmation_dialog = None;
+            }
+            Message::CloseDialog => {
+                self.tileset_dialog = None;
+                self.properties_dialog = None;
+                self.image_import_dialog = None;
+                self.canvas_size_dialog = None;
+            }
+            Message::DocumentProperties => {
+                self.properties_dialog = Some(self.file.sauce.clone().unwrap_or_default());
+            }
+            Message::PropertiesChanged(sauce) => {
+                self.properties_dialog = Some(sauce);
+            }
+            Message::ApplyProperties => {
+                if let Some(sauce) = self.properties_dialog.take() {
+                    if self.file.sauce.as_ref() != Some(&sauce) {
+                        self.file.sauce = Some(sauce);
+                        return self.set_modified();
+                    }
                 }
             }
+            Message::LoadTileset => {
+                self.tileset_dialog = Some(self.tileset_options);
+            }
+            Message::ImportFont => return actions::open_font_dialog(),
+            Message::DefaultTileset => return self.set_tileset(Tileset::default()),
+            Message::TilesetOptionsChanged(options) => {
+                self.tileset_dialog = Some(options);
+            }
+            Message::ChooseTilesetFile => {
+                if let Some(options) = self.tileset_dialog.take() {
+                    self.tileset_options = options;
+                    return actions::open_tileset_dialog();
+                }
+            }
+            Message::TilesetFileChosen(path) => {
+                let options = self.tileset_options;
+                match Tileset::from_path(path, options.tile_size, options.coverage) {
+                    Ok(tileset) => return self.set_tileset(tileset),
+                    Err(err) => {
+                        self.error_dialog = Some(ErrorDialog::new("Error loading tileset", err));
+                    }
+                }
+            }
+            Message::FontFileChosen(path) => match font::load(path) {
+                Ok(tileset) => return self.set_tileset(tileset),
+                Err(err) => {
+                    self.error_dialog = Some(ErrorDialog::new("Error importing font", err));
+                }
+            },
+            Message::SetCharMap(charmap) => return self.set_charmap(charmap),
+            Message::LoadCharMap => return actions::open_charmap_dialog(),
+            Message::CharMapFileChosen(path) => match CharMap::from_path(path) {
+                Ok(charmap) => return self.set_charmap(charmap),
+                Err(err) => {
+                    self.error_dialog = Some(ErrorDialog::new("Error loading character map", err));
+                }
+            },
+            Message::CanvasSize => {
+                let (width, height) = self.file.canvas.size();
+                self.canvas_size_dialog = Some(CanvasSizeOptions {
+                    width,
+                    height,
+                    anchor: Anchor::default(),
+                });
+            }
+            Message::CanvasSizeChanged(options) => {
+                self.canvas_size_dialog = Some(options);
+            }
+            Message::ApplyCanvasSize => {
+                if let Some(options) = self.canvas_size_dialog.take() {
+                    return self.apply(canvas::Action::Resize {
+                        width: options.width,
+                        height: options.height,
+                        anchor: options.anchor,
+                        fill: self.blank_tile(),
+                    });
+                }
+            }
+            Message::TrimCanvas => {
+                if let Some(region) = self.file.canvas.trim_region(&self.file.tileset) {
+                    return self.apply(canvas::Action::Crop(region));
+                }
+            }
+            Message::CropToSelection => {
+                if let Some(region) = self.selection.take() {
+                    return self.apply(canvas::Action::Crop(region));
+                }
+            }
+            Message::SelectTool(tool) => {
+                self.current_tool = tool;
+                return self.commit_floating();
+            }
+            Message::FillOptionsChanged(options) => {
+                self.fill_options = options;
+            }
+            Message::PickModeChanged(mode) => {
+                self.pick_mode = mode;
+            }
+            Message::SelectionChanged(selection) => {
+                self.selection = selection;
+            }
+            Message::MoveFloating { x, y } => {
+                if let Some(floating) = &mut self.floating {
+                    floating.x = x;
+                    floating.y = y;
+                }
+            }
+            Message::CommitFloating => return self.commit_floating(),
+            Message::Cut => {
+                let copy = self.copy();
+                return Command::batch([copy, self.delete_selection()]);
+            }
+            Message::Copy => return self.copy(),
+            Message::Paste => {
+                return cosmic::iced::clipboard::read_data(|contents| {
+                    cosmic::app::Message::App(Message::PasteContents(contents))
+                });
+            }
+            Message::PasteContents(contents) => {
+                let tiles = match contents {
+                    Some(ClipboardPaste::Tiles(tiles)) => tiles,
+                    Some(ClipboardPaste::Text(contents)) => {
+                        let (fg, bg) = (self.current_tile.fg, self.current_tile.bg);
+                        text::from_text(&contents, &self.file.tileset, fg, bg)
+                    }
+                    // Nothing usable outside, so fall back to what was copied in here.
+                    None => match self.clipboard.clone() {
+                        Some(tiles) => tiles,
+                        None => return Command::none(),
+                    },
+                };
+                let command = self.commit_floating();
+                // Paste over the selection, or in the top left corner without one.
+                let (x, y) = self
+                    .selection
+                    .take()
+                    .map_or((0, 0), |region| (region.x as i32, region.y as i32));
+                self.floating = Some(Floating { tiles, x, y });
+                self.current_tool = Tool::Select;
+                return command;
+            }
+            Message::Delete => return self.delete_selection(),
+            Message::SelectAll => {
+                let command = self.commit_floating();
+                let (width, height) = self.file.canvas.size();
+                self.selection = Some(Region {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                });
+                return command;
+            }
+            Message::SelectNone => {
+                // Pasted tiles that haven't been put down yet are thrown away.
+                self.floating = None;
+                self.selection = None;
+            }
+            Message::CloseRequested => return self.confirm(PendingAction::Quit),
             Message::Key(modifiers, key) => {
                 for (key_bind, action) in self.key_binds.iter() {
                     if key_bind.matches(modifiers, &key) {
@@ -124,19 +519,21 @@ impl cosmic::Application for App {
                 self.current_tile = tile;
             }
             Message::TileUp => {
-                self.current_tile.index =
-                    self.current_tile.index.saturating_sub(self.tileset.width());
+                self.current_tile.index = self
+                    .current_tile
+                    .index
+                    .saturating_sub(self.file.tileset.width());
             }
             Message::TileDown => {
-                self.current_tile.index = (self.current_tile.index + self.tileset.width())
-                    .min(self.tileset.width() * self.tileset.height() - 1);
+                self.current_tile.index = (self.current_tile.index + self.file.tileset.width())
+                    .min(self.file.tileset.tile_count() - 1);
             }
             Message::TileLeft => {
                 self.current_tile.index = self.current_tile.index.saturating_sub(1);
             }
             Message::TileRight => {
-                self.current_tile.index = (self.current_tile.index + 1)
-                    .min(self.tileset.width() * self.tileset.height() - 1);
+                self.current_tile.index =
+                    (self.current_tile.index + 1).min(self.file.tileset.tile_count() - 1);
             }
             Message::ZoomIn => {
                 self.zoom = (self.zoom + 1).min(4);
@@ -145,8 +542,48 @@ impl cosmic::Application for App {
                 self.zoom = (self.zoom - 1).max(1);
             }
             Message::CanvasClicked { x, y } => {
-                self.file.canvas.set_tile(x, y, self.current_tile);
-                self.file.modified = true;
+                let tile = self.current_tile;
+                return self.apply(match self.current_tool {
+                    Tool::FloodFill => canvas::Action::FloodFill {
+                        x,
+                        y,
+                        tile,
+                        options: self.fill_options,
+                    },
+                    _ => canvas::Action::SetTile { x, y, tile },
+                });
+            }
+            Message::PickTile { x, y } => {
+                let picked = self.file.canvas.get_tile(x, y);
+                self.current_tile = self.pick_mode.pick(self.current_tile, picked);
+            }
+            Message::StrokeStarted => {
+                self.file.history.begin_group();
+            }
+            Message::StrokeFinished => {
+                self.file.history.end_group();
+            }
+            Message::DrawShape { shape, start, end } => {
+                return self.apply(canvas::Action::Shape {
+                    shape,
+                    start,
+                    end,
+                    tile: self.current_tile,
+                });
+            }
+            Message::Undo => {
+                // Undoing a paste that hasn't been put down just drops it.
+                if self.floating.take().is_some() {
+                    return Command::none();
+                }
+                if self.file.history.undo(&mut self.file.canvas) {
+                    return self.set_modified();
+                }
+            }
+            Message::Redo => {
+                if self.file.history.redo(&mut self.file.canvas) {
+                    return self.set_modified();
+                }
             }
             Message::Todo => {
                 println!("todo");
@@ -270,10 +707,14 @@ impl cosmic::Application for App {
         ];
         column![
             row![
+                tool_palette(self.current_tool, self.fill_options, self.pick_mode),
                 tile_canvas(
+                    self.current_tool,
                     self.current_tile,
-                    &self.tileset,
+                    &self.file.tileset,
                     &self.file.canvas,
+                    self.selection,
+                    self.floating.as_ref(),
                     self.zoom
                 )
                 .apply(container)
@@ -291,7 +732,7 @@ impl cosmic::Application for App {
                     container::Appearance::default().with_background(Color::BLACK)
                 })),
                 column![
-                    tile_selector(self.current_tile, &self.tileset, 2),
+                    tile_selector(self.current_tile, &self.file.tileset, 2),
                     fg_color,
                     bg_color
                 ]
@@ -303,6 +744,44 @@ impl cosmic::Application for App {
         .apply(Element::from)
     }
 
+    fn dialog(&self) -> Option<Element<Self::Message>> {
+        if let Some(error) = &self.error_dialog {
+            return widget::dialog(error.title.clone())
+                .icon(widget::icon::from_name("dialog-error-symbolic").size(64))
+                .body(error.body.clone())
+                .primary_action(widget::button::suggested("OK").on_press(Message::DismissError))
+                .apply(Element::from)
+                .apply(Some);
+        }
+        if let Some(options) = self.tileset_dialog {
+            return Some(dialogs::tileset_options(options));
+        }
+        if let Some(options) = self.canvas_size_dialog {
+            return Some(dialogs::canvas_size(options));
+        }
+        if let Some(options) = self.image_import_dialog {
+            return Some(dialogs::image_import_options(options));
+        }
+        if let Some(sauce) = &self.properties_dialog {
+            return Some(dialogs::document_properties(sauce));
+        }
+        let action = self.confirmation_dialog?;
+        let discard = match action {
+            PendingAction::Quit => "Quit without saving",
+            _ => "Discard",
+        };
+        widget::dialog("Save changes?")
+            .icon(widget::icon::from_name("dialog-warning-symbolic").size(64))
+            .body("The document has unsaved changes, which will be lost if you don't save them.")
+            .primary_action(widget::button::suggested("Save").on_press(Message::ConfirmSave))
+            .secondary_action(
+                widget::button::destructive(discard).on_press(Message::ConfirmDiscard),
+            )
+            .tertiary_action(widget::button::standard("Cancel").on_press(Message::ConfirmCancel))
+            .apply(Element::from)
+            .apply(Some)
+    }
+
     fn core(&self) -> &cosmic::app::Core {
         &self.core
     }
@@ -315,7 +794,7 @@ impl cosmic::Application for App {
 impl App {
     fn update_title(&mut self) -> Command<Message> {
         let (header_title, window_title) = {
-            let filename = self.filename.as_deref().unwrap_or("Untitled");
+            let filename = self.file_name();
             let modified = if self.file.modified { "*" } else { "" };
             (
                 format!("{modified}{filename}"),
@@ -325,10 +804,237 @@ impl App {
         self.set_header_title(header_title);
         self.set_window_title(window_title)
     }
+
+    fn file_name(&self) -> String {
+        self.file
+            .handle
+            .as_ref()
+            .map(|handle| handle.name().into_owned())
+            .unwrap_or("Untitled".into())
+    }
+
+    /// Writes an exported document, showing an error if that fails.
+    fn export_to(&mut self, path: PathBuf, contents: String) {
+        if let Err(err) = fs::write(path, contents) {
+            self.error_dialog = Some(ErrorDialog::new("Error exporting file", err));
+        }
+    }
+
+    fn set_file(&mut self, file: FileState) -> Command<Message> {
+        self.file = file;
+        self.selection = None;
+        self.floating = None;
+        self.current_tile.index = self
+            .current_tile
+            .index
+            .min(self.file.tileset.tile_count() - 1);
+        self.update_title()
+    }
+
+    fn set_tileset(&mut self, tileset: Tileset) -> Command<Message> {
+        if tileset == self.file.tileset {
+            return Command::none();
+        }
+        self.file.tileset = tileset;
+        self.current_tile.index = self
+            .current_tile
+            .index
+            .min(self.file.tileset.tile_count() - 1);
+        self.set_modified()
+    }
+
+    fn set_charmap(&mut self, charmap: CharMap) -> Command<Message> {
+        let mut tileset = self.file.tileset.clone();
+        tileset.charmap = Some(Arc::new(charmap));
+        self.set_tileset(tileset)
+    }
+
+    /// A blank tile in the current colours, for filling space that has been cleared.
+    fn blank_tile(&self) -> Tile {
+        let blank = self.file.tileset.index_for(' ');
+        Tile {
+            index: blank.unwrap_or(Tile::default().index),
+            ..self.current_tile
+        }
+    }
+
+    /// The floating tiles, or the selected part of the canvas.
+    fn selected_tiles(&self) -> Option<Canvas> {
+        match &self.floating {
+            Some(floating) => Some(floating.tiles.clone()),
+            None => self.file.canvas.extract_region(self.selection?),
+        }
+    }
+
+    /// Copies the floating or selected tiles, to the system clipboard as well.
+    fn copy(&mut self) -> Command<Message> {
+        let Some(tiles) = self.selected_tiles() else {
+            return Command::none();
+        };
+        let contents = ClipboardCopy::new(&tiles, &self.file.tileset);
+        self.clipboard = Some(tiles);
+        cosmic::iced::clipboard::write_data(contents)
+    }
+
+    /// Drops the floating tiles, or blanks the selected part of the canvas.
+    fn delete_selection(&mut self) -> Command<Message> {
+        if self.floating.take().is_some() {
+            return Command::none();
+        }
+        match self.selection {
+            Some(region) => self.apply(canvas::Action::FillRegion {
+                region,
+                tile: self.blank_tile(),
+            }),
+            None => Command::none(),
+        }
+    }
+
+    /// Puts the floating tiles down onto the canvas, returning whether that changed anything.
+    fn put_down_floating(&mut self) -> bool {
+        let Some(Floating { tiles, x, y }) = self.floating.take() else {
+            return false;
+        };
+        let action = canvas::Action::Paste { x, y, tiles };
+        self.file.history.apply(&mut self.file.canvas, action)
+    }
+
+    fn commit_floating(&mut self) -> Command<Message> {
+        if self.put_down_floating() {
+            self.set_modified()
+        } else {
+            Command::none()
+        }
+    }
+
+    /// Applies an action to the canvas as an undoable edit.
+    fn apply(&mut self, action: canvas::Action) -> Command<Message> {
+        if self.file.history.apply(&mut self.file.canvas, action) {
+            self.set_modified()
+        } else {
+            Command::none()
+        }
+    }
+
+    fn set_modified(&mut self) -> Command<Message> {
+        if self.file.modified {
+            Command::none()
+        } else {
+            self.file.modified = true;
+            self.update_title()
+        }
+    }
+
+    fn save_to(&mut self, path: PathBuf) -> Command<Message> {
+        // The document only moves to the new path once it has been saved there.
+        let previous = self.file.handle.replace(Handle {
+            file_type: FileType::from_path(&path),
+            path,
+        });
+        // Floating tiles are saved as if put down, but only put down once the save succeeds.
+        let result = match &self.floating {
+            Some(floating) => {
+                let mut canvas = self.file.canvas.clone();
+                canvas.paste_region(floating.x, floating.y, &floating.tiles);
+                let canvas = std::mem::replace(&mut self.file.canvas, canvas);
+                let result = file_formats::save(&self.file);
+                self.file.canvas = canvas;
+                result
+            }
+            None => file_formats::save(&self.file),
+        };
+        match result {
+            Ok(()) => {
+                self.put_down_floating();
+                self.file.modified = false;
+                let command = self.update_title();
+                match self.after_save.take() {
+                    Some(action) => Command::batch([command, self.perform(action)]),
+                    None => command,
+                }
+            }
+            Err(err) => {
+                self.file.handle = previous;
+                self.after_save = None;
+                self.error_dialog = Some(ErrorDialog::new("Error saving file", err));
+                self.update_title()
+            }
+        }
+    }
+
+    /// Performs the action, first asking the user what to do with unsaved changes.
+    fn confirm(&mut self, action: PendingAction) -> Command<Message> {
+        if self.file.modified {
+            self.confirmation_dialog = Some(action);
+            Command::none()
+        } else {
+            self.perform(action)
+        }
+    }
+
+    fn perform(&mut self, action: PendingAction) -> Command<Message> {
+        match action {
+            PendingAction::New => self.set_file(FileState::default()),
+            PendingAction::Open => actions::open_dialog(),
+            PendingAction::ImportText => actions::import_text_dialog(),
+            PendingAction::ImportImage => {
+                self.image_import_dialog = Some(self.image_import_options);
+                Command::none()
+            }
+            PendingAction::Quit => iced::window::close(self.main_window_id()),
+        }
+    }
+}
+
+/// An action that would discard the current document.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum PendingAction {
+    New,
+    Open,
+    ImportText,
+    ImportImage,
+    Quit,
+}
+
+#[derive(Clone, Debug)]
+pub struct ErrorDialog {
+    title: String,
+    body: String,
+}
+
+impl ErrorDialog {
+    pub fn new(title: impl Into<String>, error: impl fmt::Display) -> ErrorDialog {
+        ErrorDialog {
+            title: title.into(),
+            body: error.to_string(),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-enum Tool {
+pub enum Tool {
     Draw,
     FloodFill,
+    /// Eyedropper, copying from the canvas into the current tile.
+    Pick,
+    Select,
+    Line,
+    Rectangle,
+    FilledRectangle,
+    Ellipse,
+    FilledEllipse,
+}
+
+impl Tool {
+    /// The shape drawn by dragging, for the shape tools.
+    fn shape(self) -> Option<Shape> {
+        match self {
+            Tool::Line => Some(Shape::Line),
+            Tool::Rectangle => Some(Shape::Rectangle { filled: false }),
+            Tool::FilledRectangle => Some(Shape::Rectangle { filled: true }),
+            Tool::Ellipse => Some(Shape::Ellipse { filled: false }),
+            Tool::FilledEllipse => Some(Shape::Ellipse { filled: true }),
+            Tool::Draw | Tool::FloodFill | Tool::Pick | Tool::Select => None,
+        }
+    }
 }
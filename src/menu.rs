@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use cosmic::widget::menu::{self, items, root, Item, MenuBar, Tree};
 use cosmic::Element;
 
+use crate::actions::CharMapPreset;
 use crate::{Action, Message};
 
 pub fn menu_bar(key_binds: &HashMap<menu::key_bind::KeyBind, Action>) -> Element<'static, Message> {
@@ -16,6 +17,19 @@ pub fn menu_bar(key_binds: &HashMap<menu::key_bind::KeyBind, Action>) -> Element
                     Item::Button("Open", Action::Open),
                     Item::Button("Save", Action::Save),
                     Item::Button("Save As", Action::SaveAs),
+                    Item::Button("Import Text", Action::ImportText),
+                    Item::Button("Import Image", Action::ImportImage),
+                    Item::Folder(
+                        "Export",
+                        vec![
+                            Item::Button("PNG Image", Action::ExportPng),
+                            Item::Button("SVG Image", Action::ExportSvg),
+                            Item::Button("HTML", Action::ExportHtml),
+                            Item::Button("Text", Action::ExportText),
+                            Item::Button("Text with Colours", Action::ExportAnsiText),
+                        ],
+                    ),
+                    Item::Button("Document Properties", Action::DocumentProperties),
                     Item::Button("Quit", Action::Quit),
                 ],
             ),
@@ -31,6 +45,43 @@ pub fn menu_bar(key_binds: &HashMap<menu::key_bind::KeyBind, Action>) -> Element
                     Item::Button("Cut", Action::Cut),
                     Item::Button("Copy", Action::Copy),
                     Item::Button("Paste", Action::Paste),
+                    Item::Button("Delete", Action::Delete),
+                    Item::Divider,
+                    Item::Button("Select All", Action::SelectAll),
+                    Item::Button("Select None", Action::SelectNone),
+                ],
+            ),
+        ),
+        Tree::with_children(
+            root("Image"),
+            items(
+                key_binds,
+                vec![
+                    Item::Button("Canvas Size", Action::CanvasSize),
+                    Item::Button("Crop to Selection", Action::CropToSelection),
+                    Item::Button("Trim Borders", Action::TrimCanvas),
+                ],
+            ),
+        ),
+        Tree::with_children(
+            root("Tileset"),
+            items(
+                key_binds,
+                vec![
+                    Item::Button("Load Tileset", Action::LoadTileset),
+                    Item::Button("Import Font", Action::ImportFont),
+                    Item::Button("Default Tileset", Action::DefaultTileset),
+                    Item::Divider,
+                    Item::Folder(
+                        "Character Map",
+                        vec![
+                            Item::Button("ASCII", Action::CharMap(CharMapPreset::Ascii)),
+                            Item::Button("Code Page 437", Action::CharMap(CharMapPreset::Cp437)),
+                            Item::Button("PETSCII", Action::CharMap(CharMapPreset::Petscii)),
+                            Item::Divider,
+                            Item::Button("Load Mapping Table", Action::LoadCharMap),
+                        ],
+                    ),
                 ],
             ),
         ),
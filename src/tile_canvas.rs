@@ -1,34 +1,58 @@
-use crate::Message;
+use crate::{Message, Tool};
 use cosmic::cosmic_theme::palette::angle::FromAngle;
 use cosmic::cosmic_theme::palette::cast::ComponentsInto;
-use cosmic::iced::{Length, Point, Rectangle, Size};
+use cosmic::iced::{Background, Border, Color, Length, Point, Rectangle, Size};
 use cosmic::iced_core::widget::tree;
-use cosmic::iced_core::{self, image::Renderer as _, layout, Renderer};
-use cosmic::iced_core::{event, Event, Shell};
+use cosmic::iced_core::{self, image::Renderer as _, layout, renderer, Renderer};
+use cosmic::iced_core::{event, keyboard, window, Event, Shell};
 use cosmic::widget::image::{FilterMethod, Handle};
 use cosmic::widget::Widget;
 use cosmic::{Apply, Element};
-use image::{GenericImageView, Pixel};
-use libmonotile::canvas::{Canvas, Tile};
+use libmonotile::canvas::{Canvas, Region, Tile};
+use libmonotile::render;
 use libmonotile::tileset::Tileset;
 
+/// Pasted tiles that can still be moved around before they are put on the canvas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Floating {
+    pub tiles: Canvas,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Floating {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x..self.x + self.tiles.width as i32).contains(&x)
+            && (self.y..self.y + self.tiles.height as i32).contains(&y)
+    }
+}
+
 pub struct TileCanvas<'a> {
+    tool: Tool,
     current_tile: Tile,
     tileset: &'a Tileset,
     canvas: &'a Canvas,
+    selection: Option<Region>,
+    floating: Option<&'a Floating>,
     scale: u8,
 }
 
 pub fn tile_canvas<'a>(
+    tool: Tool,
     current_tile: Tile,
     tileset: &'a Tileset,
     canvas: &'a Canvas,
+    selection: Option<Region>,
+    floating: Option<&'a Floating>,
     scale: u8,
 ) -> TileCanvas<'a> {
     TileCanvas {
+        tool,
         current_tile,
         tileset,
         canvas,
+        selection,
+        floating,
         scale,
     }
 }
@@ -56,6 +80,87 @@ impl<'a> TileCanvas<'a> {
         }
         shell.publish(Message::CanvasClicked { x, y });
     }
+
+    /// The tile under a position relative to the canvas, which may be outside it.
+    fn tile_at(&self, position: Point) -> (i32, i32) {
+        let scale = self.scale as f32;
+        let x = position.x / scale / self.tileset.tile_size.0 as f32;
+        let y = position.y / scale / self.tileset.tile_size.1 as f32;
+        (x.floor() as i32, y.floor() as i32)
+    }
+
+    /// Clamps a tile position to the canvas.
+    fn clamp(&self, (x, y): (i32, i32)) -> (u32, u32) {
+        let (width, height) = self.canvas.size();
+        (
+            (x.max(0) as u32).min(width - 1),
+            (y.max(0) as u32).min(height - 1),
+        )
+    }
+
+    fn select_to(&self, shell: &mut Shell<'_, Message>, start: (u32, u32), end: (i32, i32)) {
+        let region = Region::from_corners(start, self.clamp(end));
+        if self.selection != Some(region) {
+            shell.publish(Message::SelectionChanged(Some(region)));
+        }
+    }
+
+    fn move_floating(&self, shell: &mut Shell<'_, Message>, grab: (i32, i32), to: (i32, i32)) {
+        let Some(floating) = self.floating else {
+            return;
+        };
+        let (x, y) = (to.0 - grab.0, to.1 - grab.1);
+        if (x, y) != (floating.x, floating.y) {
+            shell.publish(Message::MoveFloating { x, y });
+        }
+    }
+
+    /// Rectangle covering a region of tiles, relative to the canvas.
+    fn tile_bounds(&self, x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        let scale = self.scale as f32;
+        let tile_width = self.tileset.tile_size.0 as f32 * scale;
+        let tile_height = self.tileset.tile_size.1 as f32 * scale;
+        Rectangle {
+            x: x as f32 * tile_width,
+            y: y as f32 * tile_height,
+            width: width as f32 * tile_width,
+            height: height as f32 * tile_height,
+        }
+    }
+}
+
+/// What a mouse drag on the canvas is doing.
+#[derive(Copy, Clone, Debug)]
+enum Drag {
+    Stroke,
+    /// Selecting from the tile where the drag started.
+    Select {
+        start: (u32, u32),
+    },
+    /// Moving the floating tiles, holding them at an offset from their top left corner.
+    Move {
+        grab: (i32, i32),
+    },
+    /// Drawing a shape, shown as a preview until the button is released.
+    Shape {
+        start: (u32, u32),
+        end: (u32, u32),
+    },
+}
+
+fn outline(renderer: &mut cosmic::Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border: Border {
+                color,
+                width: 1.0,
+                radius: 0.0.into(),
+            },
+            shadow: Default::default(),
+        },
+        Background::Color(Color::TRANSPARENT),
+    );
 }
 
 impl<'a> Widget<Message, cosmic::Theme, cosmic::iced::Renderer> for TileCanvas<'a> {
@@ -100,40 +205,103 @@ impl<'a> Widget<Message, cosmic::Theme, cosmic::iced::Renderer> for TileCanvas<'
             Event::Mouse(mouse_event) => {
                 use iced_core::mouse;
                 let position = cursor.position_in(layout.bounds());
+                // Drags carry on outside the canvas, so they use the unclamped position.
+                let relative = cursor
+                    .position()
+                    .map(|position| position - (layout.position() - Point::ORIGIN));
                 match mouse_event {
-                    mouse::Event::CursorMoved { position: _ } => {
-                        if state.dragging {
+                    mouse::Event::CursorMoved { position: _ } => match (state.drag, relative) {
+                        (Some(Drag::Stroke), _) => {
                             if let Some(position) = position {
                                 self.set_tile(shell, position);
                             }
                             event::Status::Captured
-                        } else {
-                            event::Status::Ignored
                         }
-                    }
-                    mouse::Event::ButtonPressed(mouse::Button::Left) => {
-                        if let Some(position) = position {
-                            state.dragging = true;
-                            self.set_tile(shell, position);
+                        (Some(Drag::Select { start }), Some(relative)) => {
+                            self.select_to(shell, start, self.tile_at(relative));
                             event::Status::Captured
-                        } else {
-                            event::Status::Ignored
                         }
-                    }
-                    mouse::Event::ButtonReleased(mouse::Button::Left) => {
-                        if state.dragging {
-                            state.dragging = false;
+                        (Some(Drag::Move { grab }), Some(relative)) => {
+                            self.move_floating(shell, grab, self.tile_at(relative));
                             event::Status::Captured
-                        } else {
-                            event::Status::Ignored
                         }
+                        (Some(Drag::Shape { start, end }), Some(relative)) => {
+                            let tile = self.clamp(self.tile_at(relative));
+                            if tile != end {
+                                state.drag = Some(Drag::Shape { start, end: tile });
+                                shell.request_redraw(window::RedrawRequest::NextFrame);
+                            }
+                            event::Status::Captured
+                        }
+                        _ => event::Status::Ignored,
+                    },
+                    mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                        let Some(position) = position else {
+                            return event::Status::Ignored;
+                        };
+                        let (x, y) = self.tile_at(position);
+                        if self.tool == Tool::Pick || state.modifiers.control() {
+                            let (x, y) = self.clamp((x, y));
+                            shell.publish(Message::PickTile { x, y });
+                            return event::Status::Captured;
+                        }
+                        match self.tool {
+                            Tool::Select => match self.floating {
+                                Some(floating) if floating.contains(x, y) => {
+                                    let grab = (x - floating.x, y - floating.y);
+                                    state.drag = Some(Drag::Move { grab });
+                                }
+                                _ => {
+                                    if self.floating.is_some() {
+                                        shell.publish(Message::CommitFloating);
+                                    }
+                                    let start = self.clamp((x, y));
+                                    state.drag = Some(Drag::Select { start });
+                                    shell.publish(Message::SelectionChanged(Some(
+                                        Region::from_corners(start, start),
+                                    )));
+                                }
+                            },
+                            Tool::FloodFill => {
+                                let (x, y) = self.clamp((x, y));
+                                shell.publish(Message::CanvasClicked { x, y });
+                            }
+                            tool if tool.shape().is_some() => {
+                                let start = self.clamp((x, y));
+                                state.drag = Some(Drag::Shape { start, end: start });
+                            }
+                            _ => {
+                                state.drag = Some(Drag::Stroke);
+                                shell.publish(Message::StrokeStarted);
+                                self.set_tile(shell, position);
+                            }
+                        }
+                        event::Status::Captured
                     }
+                    mouse::Event::ButtonReleased(mouse::Button::Left) => match state.drag.take() {
+                        Some(Drag::Stroke) => {
+                            shell.publish(Message::StrokeFinished);
+                            event::Status::Captured
+                        }
+                        Some(Drag::Shape { start, end }) => {
+                            if let Some(shape) = self.tool.shape() {
+                                shell.publish(Message::DrawShape { shape, start, end });
+                            }
+                            event::Status::Captured
+                        }
+                        Some(_) => event::Status::Captured,
+                        None => event::Status::Ignored,
+                    },
                     _ => event::Status::Ignored,
                 }
             }
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = modifiers;
+                event::Status::Ignored
+            }
             Event::Window(_, window_event) => match window_event {
                 iced_core::window::Event::RedrawRequested(_) => {
-                    state.update(self.tileset, self.current_tile, self.canvas);
+                    state.update(self.tileset, self.current_tile, self.canvas, self.floating);
                     event::Status::Ignored
                 }
                 _ => event::Status::Ignored,
@@ -164,6 +332,42 @@ impl<'a> Widget<Message, cosmic::Theme, cosmic::iced::Renderer> for TileCanvas<'
                 [0.0; 4],
             );
         });
+        let offset = layout.position() - Point::ORIGIN;
+        if let Some(floating) = self.floating {
+            let (width, height) = floating.tiles.size();
+            let bounds = self.tile_bounds(floating.x, floating.y, width, height) + offset;
+            renderer.with_layer(layout.bounds(), |renderer| {
+                renderer.draw(
+                    state.floating_image.clone(),
+                    FilterMethod::Nearest,
+                    bounds,
+                    [0.0; 4],
+                );
+                outline(renderer, bounds, Color::from_rgb8(0xff, 0x80, 0x00));
+            });
+        }
+        if let Some(selection) = self.selection {
+            let (width, height) = self.canvas.size();
+            if let Some(region) = selection.clamp(width, height) {
+                let (x, y) = (region.x as i32, region.y as i32);
+                let bounds = self.tile_bounds(x, y, region.width, region.height) + offset;
+                outline(renderer, bounds, Color::WHITE);
+            }
+        }
+        if let (Some(Drag::Shape { start, end }), Some(shape)) = (state.drag, self.tool.shape()) {
+            for (x, y) in shape.points(start, end) {
+                renderer.draw(
+                    state.tile_image.clone(),
+                    FilterMethod::Nearest,
+                    self.tile_bounds(x as i32, y as i32, 1, 1) + offset,
+                    [0.0; 4],
+                );
+            }
+        }
+        // Only tools that put the current tile down show it under the cursor.
+        if matches!(self.tool, Tool::Select | Tool::Pick) || state.modifiers.control() {
+            return;
+        }
         if let Some(mut position) = cursor.position_in(layout.bounds()) {
             let scale = self.scale as f32;
             let tile_width = self.tileset.tile_size.0 as f32 * scale;
@@ -202,10 +406,14 @@ impl<'a> From<TileCanvas<'a>> for Element<'a, Message> {
 pub struct State {
     canvas_image: Handle,
     tile_image: Handle,
+    floating_image: Handle,
     previous_tileset: Option<Tileset>,
     previous_tile: Option<Tile>,
     previous_canvas: Option<Canvas>,
-    dragging: bool,
+    previous_floating: Option<Canvas>,
+    drag: Option<Drag>,
+    /// Held modifiers, as Ctrl-click picks a tile with any tool.
+    modifiers: keyboard::Modifiers,
 }
 
 impl State {
@@ -213,44 +421,38 @@ impl State {
         State {
             canvas_image: Handle::from_pixels(0, 0, []),
             tile_image: Handle::from_pixels(0, 0, []),
+            floating_image: Handle::from_pixels(0, 0, []),
             previous_tileset: None,
             previous_tile: None,
             previous_canvas: None,
-            dragging: false,
+            previous_floating: None,
+            drag: None,
+            modifiers: keyboard::Modifiers::empty(),
         }
     }
 
-    pub fn update(&mut self, tileset: &Tileset, current_tile: Tile, canvas: &Canvas) {
-        self.update_tile(tileset, current_tile);
-        if self.previous_tileset.as_ref() == Some(tileset)
-            && self.previous_canvas.as_ref() == Some(canvas)
-        {
+    pub fn update(
+        &mut self,
+        tileset: &Tileset,
+        current_tile: Tile,
+        canvas: &Canvas,
+        floating: Option<&Floating>,
+    ) {
+        let tileset_changed = self.previous_tileset.as_ref() != Some(tileset);
+        self.update_tile(tileset, current_tile, tileset_changed);
+        self.update_floating(
+            tileset,
+            floating.map(|floating| &floating.tiles),
+            tileset_changed,
+        );
+        if !tileset_changed && self.previous_canvas.as_ref() == Some(canvas) {
             return;
         }
         self.previous_tileset = Some(tileset.clone());
         self.previous_canvas = Some(canvas.clone());
 
-        let mut canvas_image: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> =
-            image::ImageBuffer::from_pixel(
-                canvas.width * tileset.tile_size.0,
-                canvas.height * tileset.tile_size.1,
-                image::Rgba([0, 0, 0, 255]),
-            );
-        for (x, y, tile) in canvas.tiles() {
-            if let Some(tile_image) = tileset.tile(tile.index) {
-                let xoff = x * tileset.tile_size.0;
-                let yoff = y * tileset.tile_size.1;
-                let mut foreground: image::Rgba<u8> = tile.fg.into();
-                for (x, y, blend) in tile_image.pixels() {
-                    foreground.0[3] = blend.0[0];
-                    let mut color: image::Rgba<u8> = tile.bg.into();
-                    color.blend(&foreground);
-                    let x = x + xoff;
-                    let y = y + yoff;
-                    canvas_image.put_pixel(x, y, color);
-                }
-            }
-        }
+        // Canvases are kept far below the render limit, but show nothing rather than panic.
+        let canvas_image = render::render(canvas, tileset, 1).unwrap_or_default();
         self.canvas_image = Handle::from_pixels(
             canvas_image.width(),
             canvas_image.height(),
@@ -258,23 +460,29 @@ impl State {
         );
     }
 
-    fn update_tile(&mut self, tileset: &Tileset, current_tile: Tile) {
-        if self.previous_tile == Some(current_tile) {
+    fn update_floating(
+        &mut self,
+        tileset: &Tileset,
+        tiles: Option<&Canvas>,
+        tileset_changed: bool,
+    ) {
+        if !tileset_changed && self.previous_floating.as_ref() == tiles {
             return;
         }
-        self.previous_tile = Some(current_tile);
-        let tile_size = tileset.tile_size;
-        let mut fg: image::Rgba<u8> = current_tile.fg.into();
-        let bg = current_tile.bg.into();
-        let mut tile_image: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> =
-            image::ImageBuffer::from_pixel(tile_size.0, tile_size.1, bg);
-        for (pixel, (_, _, image::Luma([blend]))) in tile_image
-            .pixels_mut()
-            .zip(tileset.tile(current_tile.index).unwrap().pixels())
-        {
-            fg.0[3] = blend;
-            pixel.blend(&fg);
+        self.previous_floating = tiles.cloned();
+        let Some(tiles) = tiles else {
+            return;
+        };
+        let image = render::render(tiles, tileset, 1).unwrap_or_default();
+        self.floating_image = Handle::from_pixels(image.width(), image.height(), image.into_raw());
+    }
+
+    fn update_tile(&mut self, tileset: &Tileset, current_tile: Tile, tileset_changed: bool) {
+        if !tileset_changed && self.previous_tile == Some(current_tile) {
+            return;
         }
+        self.previous_tile = Some(current_tile);
+        let tile_image = render::render_tile(current_tile, tileset);
         self.tile_image = Handle::from_pixels(
             tile_image.width(),
             tile_image.height(),
@@ -0,0 +1,75 @@
+use cosmic::iced::Length;
+use cosmic::iced_widget::{column, Column};
+use cosmic::widget::{self, text};
+use cosmic::{Apply, Element};
+use libmonotile::canvas::{FillOptions, FillReach, TilePart};
+
+use crate::{Message, Tool};
+
+const TOOLS: [(&str, Tool); 9] = [
+    ("Draw", Tool::Draw),
+    ("Fill", Tool::FloodFill),
+    ("Pick", Tool::Pick),
+    ("Select", Tool::Select),
+    ("Line", Tool::Line),
+    ("Rectangle", Tool::Rectangle),
+    ("Filled Rectangle", Tool::FilledRectangle),
+    ("Ellipse", Tool::Ellipse),
+    ("Filled Ellipse", Tool::FilledEllipse),
+];
+
+static PART_NAMES: [&str; 4] = ["Whole tile", "Glyph", "Foreground", "Background"];
+static REACH_NAMES: [&str; 3] = ["Through sides", "Through corners", "Whole canvas"];
+
+/// Buttons for picking the tool, followed by the settings of the current one.
+pub fn tool_palette(tool: Tool, fill: FillOptions, pick: TilePart) -> Element<'static, Message> {
+    let buttons = TOOLS.into_iter().map(|(label, button_tool)| {
+        let button = if button_tool == tool {
+            widget::button::suggested(label)
+        } else {
+            widget::button::standard(label)
+        };
+        button
+            .on_press(Message::SelectTool(button_tool))
+            .width(Length::Fill)
+            .into()
+    });
+    let mut palette = Column::with_children(buttons).spacing(4);
+    if tool == Tool::FloodFill {
+        let matching = TilePart::ALL.iter().position(|&m| m == fill.matching);
+        let reach = FillReach::ALL.iter().position(|&r| r == fill.reach);
+        palette = palette.push(
+            column![
+                text("Match"),
+                widget::dropdown(&PART_NAMES, matching, move |index| {
+                    Message::FillOptionsChanged(FillOptions {
+                        matching: TilePart::ALL[index],
+                        ..fill
+                    })
+                }),
+                text("Spread"),
+                widget::dropdown(&REACH_NAMES, reach, move |index| {
+                    Message::FillOptionsChanged(FillOptions {
+                        reach: FillReach::ALL[index],
+                        ..fill
+                    })
+                }),
+            ]
+            .spacing(4),
+        );
+    }
+    if tool == Tool::Pick {
+        let selected = TilePart::ALL.iter().position(|&mode| mode == pick);
+        palette = palette.push(
+            column![
+                text("Pick"),
+                widget::dropdown(&PART_NAMES, selected, |index| {
+                    Message::PickModeChanged(TilePart::ALL[index])
+                }),
+                text("Ctrl-click picks with any tool."),
+            ]
+            .spacing(4),
+        );
+    }
+    palette.width(150).padding(4).apply(Element::from)
+}